@@ -0,0 +1,33 @@
+use crate::error::repository_error;
+use crate::AdminIssuer;
+use account_state::error::AccountError;
+use auth_lib::JwtToken;
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use state_repository::causation::CausationNode;
+use state_repository::StateRepository;
+use uuid::Uuid;
+
+pub fn get_route() -> Vec<Route> {
+    routes![causation]
+}
+
+/// Every command and event of a correlation across the streams, as the trees of what
+/// caused what.
+#[get("/causation/<correlation_id>")]
+pub async fn causation(
+    state_repository: &State<StateRepository>,
+    _token: JwtToken<AdminIssuer>,
+    correlation_id: &str,
+) -> Result<Json<Vec<CausationNode>>, AccountError> {
+    let correlation_id = Uuid::parse_str(correlation_id).map_err(|e| {
+        AccountError::BadRequest(format!("bad correlation id {correlation_id} : {e}"))
+    })?;
+
+    let tree = state_repository
+        .causation_tree(correlation_id)
+        .await
+        .map_err(repository_error)?;
+
+    Ok(Json(tree))
+}
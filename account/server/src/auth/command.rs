@@ -1,4 +1,6 @@
 use crate::auth::get_key;
+use crate::auth::idempotency::IdempotencyKey;
+use crate::error::command_error;
 use account_state::error::AccountError;
 
 use rocket::serde::json::Json;
@@ -10,6 +12,7 @@ use account_shared::{AccountCommand, CreateAccount, Login};
 use account_state::state::AccountState;
 use auth_lib::JwtToken;
 use state_repository::model_key::ModelKey;
+use state_repository::options::CommandOptions;
 use state_repository::StateRepository;
 
 #[post("/", format = "json", data = "<command>")]
@@ -18,7 +21,10 @@ pub async fn handle_anonymous(
     maria_db: &State<MariadDb>,
     command: Json<AccountCommand>,
     token: Option<JwtToken<AccountIssuer>>,
+    idempotency_key: Option<IdempotencyKey>,
 ) -> Result<String, AccountError> {
+    let options = IdempotencyKey::options(idempotency_key);
+
     match token {
         None => match command.0 {
             AccountCommand::CreateAccount(cmd) => create(state_repository, maria_db, cmd).await,
@@ -30,28 +36,44 @@ pub async fn handle_anonymous(
                 "cannot remove quantity without id".to_string(),
             )),
         },
-        Some(token) => match command.0 {
-            AccountCommand::CreateAccount(_) => {
-                Err(AccountError::Other("cannot create with id".to_string()))
-            }
-            AccountCommand::Login(_) => {
-                Err(AccountError::Other("cannot login with id".to_string()))
-            }
-            AccountCommand::AddReputation(cmd) => {
-                let key = ModelKey::new("account".to_string(), token.uuid().to_string());
-                state_repository
-                    .add_command::<AccountState>(&key, AccountCommand::AddReputation(cmd), None)
-                    .await?;
-                Ok("added".to_string())
-            }
-            AccountCommand::RemoveReputation(cmd) => {
-                let key = ModelKey::new("account".to_string(), token.uuid().to_string());
-                state_repository
-                    .add_command::<AccountState>(&key, AccountCommand::RemoveReputation(cmd), None)
-                    .await?;
-                Ok("removed".to_string())
+        Some(token) => {
+            let options = options.actor(token.uuid());
+
+            match command.0 {
+                AccountCommand::CreateAccount(_) => {
+                    Err(AccountError::Other("cannot create with id".to_string()))
+                }
+                AccountCommand::Login(_) => {
+                    Err(AccountError::Other("cannot login with id".to_string()))
+                }
+                AccountCommand::AddReputation(cmd) => {
+                    let key = ModelKey::new("account".to_string(), token.uuid().to_string());
+                    state_repository
+                        .add_command_with_options::<AccountState>(
+                            &key,
+                            AccountCommand::AddReputation(cmd),
+                            None,
+                            &options,
+                        )
+                        .await
+                        .map_err(command_error)?;
+                    Ok("added".to_string())
+                }
+                AccountCommand::RemoveReputation(cmd) => {
+                    let key = ModelKey::new("account".to_string(), token.uuid().to_string());
+                    state_repository
+                        .add_command_with_options::<AccountState>(
+                            &key,
+                            AccountCommand::RemoveReputation(cmd),
+                            None,
+                            &options,
+                        )
+                        .await
+                        .map_err(command_error)?;
+                    Ok("removed".to_string())
+                }
             }
-        },
+        }
     }
 }
 
@@ -82,9 +104,17 @@ SELECT uuid, pseudo FROM `user` WHERE email like ? and password like ? limit 1;
         password: "***".to_string(),
     });
 
+    let options = CommandOptions::default().actor(exists.uuid.clone());
+
     state_repository
-        .add_command::<AccountState>(&get_key(Some(exists.uuid.clone())), command, None)
-        .await?;
+        .add_command_with_options::<AccountState>(
+            &get_key(Some(exists.uuid.clone())),
+            command,
+            None,
+            &options,
+        )
+        .await
+        .map_err(command_error)?;
 
     Ok(JwtToken::<AccountIssuer>::create(exists.uuid))
 }
@@ -159,9 +189,12 @@ VALUES (?, ?, ?, ?, ?);
         password: "***".to_string(),
     });
 
+    let options = CommandOptions::default().actor(id.clone());
+
     state_repository
-        .add_command::<AccountState>(&key, command, None)
-        .await?;
+        .add_command_with_options::<AccountState>(&key, command, None, &options)
+        .await
+        .map_err(command_error)?;
 
     Ok(JwtToken::<AccountIssuer>::create(id))
 }
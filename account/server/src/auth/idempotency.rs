@@ -0,0 +1,28 @@
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use state_repository::options::CommandOptions;
+
+/// `Idempotency-Key` header of a command, a command sent again with the same key is
+/// only applied once.
+pub struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    pub fn options(key: Option<IdempotencyKey>) -> CommandOptions {
+        match key {
+            None => CommandOptions::default(),
+            Some(IdempotencyKey(key)) => CommandOptions::default().idempotency_key(key),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one("idempotency-key") {
+            Some(key) if !key.is_empty() => Outcome::Success(IdempotencyKey(key.to_string())),
+            _ => Outcome::Forward(()),
+        }
+    }
+}
@@ -1,4 +1,6 @@
 mod command;
+mod idempotency;
+pub mod projection;
 mod query;
 
 use crate::auth::command::handle_anonymous;
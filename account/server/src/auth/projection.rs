@@ -0,0 +1,40 @@
+use rocket::async_trait;
+use state_repository::projection::Projection;
+use state_repository::storage::StoredEvent;
+use state_repository::StateRepository;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const ACCOUNT_CREATED: &str = "evt.account.Created";
+
+/// Number of accounts created, kept up to date from the creation events.
+#[derive(Default)]
+pub struct AccountCount {
+    count: AtomicU64,
+}
+
+impl AccountCount {
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl Projection for AccountCount {
+    fn name(&self) -> &str {
+        "account-count"
+    }
+
+    fn event_types(&self) -> Vec<String> {
+        vec![ACCOUNT_CREATED.to_string()]
+    }
+
+    async fn apply(&self, _: &StateRepository, _: &StoredEvent) -> anyhow::Result<()> {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn reset(&self) -> anyhow::Result<()> {
+        self.count.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+}
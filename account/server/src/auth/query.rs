@@ -1,4 +1,6 @@
 use crate::auth::get_key;
+use crate::auth::projection::AccountCount;
+use crate::error::repository_error;
 use account_shared::AccountDto;
 use account_state::error::AccountError;
 
@@ -8,6 +10,7 @@ use auth_lib::JwtToken;
 use rocket::serde::json::Json;
 use rocket::State;
 use state_repository::StateRepository;
+use std::sync::Arc;
 
 #[get("/account")]
 pub async fn account(
@@ -17,25 +20,12 @@ pub async fn account(
     let account = state_repository
         .get_model::<AccountState>(&get_key(Some(token.uuid().to_string())))
         .await
-        .unwrap();
+        .map_err(repository_error)?;
 
     Ok(Json(account.state().dto()))
 }
 
 #[get("/header-count")]
-pub async fn register(state_repository: &State<StateRepository>) -> String {
-    let db = state_repository.event_db().clone();
-
-    let mut res = db
-        .read_stream("$et-AccountCreated", &Default::default())
-        .await
-        .unwrap();
-
-    let mut nb = 0;
-
-    while res.next().await.unwrap().is_some() {
-        nb += 1;
-    }
-
-    format!("number of header : {:?}", nb)
+pub async fn register(account_count: &State<Arc<AccountCount>>) -> String {
+    format!("number of header : {:?}", account_count.count())
 }
@@ -0,0 +1,26 @@
+use account_state::error::AccountError;
+use state_repository::error::{CommandError, RepositoryError};
+
+/// Answer to a command refused by the account or failing in the state repository.
+pub fn command_error(error: CommandError<AccountError>) -> AccountError {
+    match error {
+        CommandError::Rejected(error) => error,
+        CommandError::Repository(error) => repository_error(error),
+    }
+}
+
+/// Answer to a failure of the state repository.
+pub fn repository_error(error: RepositoryError) -> AccountError {
+    match error {
+        RepositoryError::Conflict(e) => AccountError::Conflict(e),
+        RepositoryError::RetryExhausted { .. } => AccountError::Conflict(error.to_string()),
+        RepositoryError::StoreUnavailable(_)
+        | RepositoryError::Snapshot(_)
+        | RepositoryError::Checkpoint(_)
+        | RepositoryError::Schedule(_)
+        | RepositoryError::Timeout(_) => AccountError::Unavailable(error.to_string()),
+        RepositoryError::Deserialization(_)
+        | RepositoryError::Projection(_)
+        | RepositoryError::Processing(_) => AccountError::Other(error.to_string()),
+    }
+}
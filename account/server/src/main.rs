@@ -1,19 +1,28 @@
 #[macro_use]
 extern crate rocket;
 
+use crate::auth::projection::AccountCount;
 use auth_lib::Issuer;
 use dotenvy::dotenv;
 use eventstore::Client;
 use global_config::Config;
+use rocket::fairing::AdHoc;
 use rocket::fs::{relative, FileServer};
 use rocket::http::Method;
 use rocket::response::content;
 use rocket_cors::{AllowedHeaders, AllowedOrigins};
 use sqlx::mysql::MySqlPool;
 use sqlx::{MySql, Pool};
+use state_repository::checkpoint::InMemoryCheckpointStore;
+use state_repository::consumer::Supervisor;
+use state_repository::projection::ProjectionRunner;
+use state_repository::snapshot::RedisSnapshotStore;
 use state_repository::StateRepository;
+use std::sync::Arc;
 
+mod admin;
 mod auth;
+mod error;
 
 pub struct MariadDb {
     pub db: Pool<MySql>,
@@ -37,12 +46,26 @@ impl Issuer for AccountIssuer {
     }
 }
 
+pub struct AdminIssuer {}
+
+impl Issuer for AdminIssuer {
+    fn name() -> String {
+        dotenvy::var("ISSUER_ADMIN_NAME").unwrap()
+    }
+
+    fn secret() -> String {
+        dotenvy::var("ISSUER_ADMIN_SECRET").unwrap()
+    }
+}
+
 #[launch]
 fn rocket() -> _ {
     dotenv().ok();
 
     AccountIssuer::name();
     AccountIssuer::secret();
+    AdminIssuer::name();
+    AdminIssuer::secret();
 
     let config = Config::load();
 
@@ -50,9 +73,15 @@ fn rocket() -> _ {
     let settings = config.event_store().parse().unwrap();
     let event_db = Client::new(settings).unwrap();
 
-    let cache_db = redis::Client::open(config.redis()).unwrap();
+    let snapshot_db = RedisSnapshotStore::new(redis::Client::open(config.redis()).unwrap());
+
+    let state_repository = StateRepository::new(event_db, snapshot_db).with_service("account");
 
-    let state_repository = StateRepository::new(event_db, cache_db);
+    // the count lives in memory, it is rebuilt from the first events on each start
+    let account_count = Arc::new(AccountCount::default());
+    let projections =
+        ProjectionRunner::new(state_repository.clone(), InMemoryCheckpointStore::default());
+    let supervisor = Supervisor::new(state_repository.clone(), InMemoryCheckpointStore::default());
 
     let mariadb_url = format!("{}/account", config.mysql());
     let pool = MySqlPool::connect_lazy(&mariadb_url).unwrap();
@@ -77,9 +106,17 @@ fn rocket() -> _ {
     rocket::custom(figment)
         .manage(state_repository)
         .manage(MariadDb::new(pool))
+        .manage(account_count.clone())
         .mount("/api", auth::get_route())
+        .mount("/admin", admin::get_route())
         .mount("/", FileServer::from(relative!("web")))
         .attach(cors)
+        .attach(AdHoc::on_liftoff("Projections", |_| {
+            Box::pin(async move {
+                // restarted by the supervisor when failing, its status is kept there
+                projections.supervise(&supervisor, account_count);
+            })
+        }))
         .register("/", catchers![general_not_found])
 }
 
@@ -20,21 +20,11 @@ pub struct Login {
     pub password: String,
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, Command)]
 pub enum AccountCommand {
+    #[command(name = "Create")]
     CreateAccount(CreateAccount),
     Login(Login),
     AddReputation(usize),
     RemoveReputation(usize),
 }
-
-impl Command for AccountCommand {
-    fn command_name(&self) -> &'static str {
-        match self {
-            AccountCommand::CreateAccount(_) => "Create",
-            AccountCommand::AddReputation(_) => "AddReputation",
-            AccountCommand::RemoveReputation(_) => "RemoveReputation",
-            AccountCommand::Login(_) => "Login",
-        }
-    }
-}
@@ -5,18 +5,27 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Responder, Clone, Debug, Deserialize, Eq, PartialEq, Serialize, Display)]
 pub enum AccountError {
+    #[response(status = 400)]
+    BadRequest(String),
     #[response(status = 404)]
     NotFound(String),
     #[response(status = 500)]
     AlreadyExist(String),
-    #[response(status = 500)]
+    #[response(status = 422)]
     WrongQuantity(String),
+    #[response(status = 409)]
+    Conflict(String),
+    #[response(status = 503)]
+    Unavailable(String),
     #[response(status = 500)]
     Other(String),
 }
 
 impl From<anyhow::Error> for AccountError {
-    fn from(_: Error) -> Self {
-        Self::Other("Oupsi".to_string())
+    fn from(error: Error) -> Self {
+        match error.downcast::<AccountError>() {
+            Ok(error) => error,
+            Err(error) => Self::Other(error.to_string()),
+        }
     }
 }
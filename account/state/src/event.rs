@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use state::Event;
 use uuid::Uuid;
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, Event)]
 pub enum AccountEvent {
     Logged(LoggedIn),
     Created(Created),
@@ -10,17 +10,6 @@ pub enum AccountEvent {
     ReputationRemoved(usize),
 }
 
-impl Event for AccountEvent {
-    fn event_name(&self) -> &'static str {
-        match self {
-            AccountEvent::Logged(_) => "Logged",
-            AccountEvent::Created(_) => "Created",
-            AccountEvent::ReputationAdded(_) => "ReputationAdded",
-            AccountEvent::ReputationRemoved(_) => "ReputationRemoved",
-        }
-    }
-}
-
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Created {
     pub uuid: Uuid,
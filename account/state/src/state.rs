@@ -1,11 +1,10 @@
-use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use uuid::Uuid;
 
 use account_shared::{AccountCommand, AccountDto};
-use anyhow::{anyhow, Result};
 use rocket::serde::{Deserialize, Serialize};
-use state::State;
+use state::{CommandContext, State};
 
 use crate::event::{Created, LoggedIn};
 use crate::{AccountError, AccountEvent};
@@ -48,6 +47,7 @@ impl AccountState {
 impl State for AccountState {
     type Event = AccountEvent;
     type Command = AccountCommand;
+    type Error = AccountError;
 
     fn name_prefix() -> &'static str {
         "account"
@@ -70,46 +70,48 @@ impl State for AccountState {
         }
     }
 
-    fn try_command(&self, command: Self::Command) -> Result<Vec<Self::Event>> {
+    fn try_command(
+        &self,
+        command: Self::Command,
+        context: &CommandContext,
+    ) -> Result<Vec<Self::Event>, Self::Error> {
         match command {
             AccountCommand::CreateAccount(create) => {
                 if !self.pseudo.is_empty() {
-                    Err(anyhow!(AccountError::Other(
+                    Err(AccountError::Other(
                         "account already has a pseudo".to_string(),
-                    )))
+                    ))
                 } else {
-                    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+                    let now = now_secs(context)?;
 
                     Ok(vec![AccountEvent::Created(Created {
-                        uuid: Uuid::new_v4(),
+                        uuid: context.new_id(),
                         pseudo: create.pseudo,
-                        time: now.as_secs(),
+                        time: now,
                     })])
                 }
             }
             AccountCommand::Login(_login) => {
-                let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+                let now = now_secs(context)?;
 
-                Ok(vec![AccountEvent::Logged(LoggedIn {
-                    time: now.as_secs(),
-                })])
+                Ok(vec![AccountEvent::Logged(LoggedIn { time: now })])
             }
             AccountCommand::AddReputation(nb) => {
                 if self.reputation.checked_add(nb).is_none() {
-                    Err(anyhow!(AccountError::WrongQuantity(format!(
+                    Err(AccountError::WrongQuantity(format!(
                         "cannot add {} to {}",
                         nb, self.reputation
-                    ))))
+                    )))
                 } else {
                     Ok(vec![AccountEvent::ReputationAdded(nb)])
                 }
             }
             AccountCommand::RemoveReputation(nb) => {
                 if nb > self.reputation {
-                    Err(anyhow!(AccountError::WrongQuantity(format!(
+                    Err(AccountError::WrongQuantity(format!(
                         "cannot remove {} from {}",
                         nb, self.reputation
-                    ))))
+                    )))
                 } else {
                     Ok(vec![AccountEvent::ReputationRemoved(nb)])
                 }
@@ -117,7 +119,16 @@ impl State for AccountState {
         }
     }
 
-    fn state_cache_interval() -> Option<u64> {
+    fn snapshot_interval() -> Option<u64> {
         Some(20)
     }
 }
+
+/// Seconds since the unix epoch given by the clock of the command.
+fn now_secs(context: &CommandContext) -> Result<u64, AccountError> {
+    context
+        .now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .map_err(|e| AccountError::Other(format!("clock before the unix epoch : {e}")))
+}
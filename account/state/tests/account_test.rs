@@ -0,0 +1,47 @@
+use account_shared::AccountCommand::{CreateAccount, Login};
+use account_shared::{CreateAccount as Create, Login as LoginData};
+use account_state::event::AccountEvent::{Created, Logged};
+use account_state::event::{Created as CreatedData, LoggedIn};
+use account_state::state::AccountState;
+use state::testing::given;
+use state::CommandContext;
+use std::time::{Duration, UNIX_EPOCH};
+use uuid::Uuid;
+
+fn context() -> CommandContext {
+    CommandContext::fixed(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+}
+
+#[test]
+fn create_case() {
+    let state = given::<AccountState>([])
+        .with_context(context())
+        .when(CreateAccount(Create {
+            pseudo: "jean".to_string(),
+            email: "jean@mail.com".to_string(),
+            password: "secret".to_string(),
+        }))
+        .then_events([Created(CreatedData {
+            uuid: Uuid::from_u128(1),
+            pseudo: "jean".to_string(),
+            time: 1_700_000_000,
+        })]);
+
+    assert_eq!(state.uuid(), Uuid::from_u128(1));
+    assert_eq!(state.register_at(), 1_700_000_000);
+}
+
+#[test]
+fn login_case() {
+    let state = given::<AccountState>([])
+        .with_context(context())
+        .when(Login(LoginData {
+            email: "jean@mail.com".to_string(),
+            password: "secret".to_string(),
+        }))
+        .then_events([Logged(LoggedIn {
+            time: 1_700_000_000,
+        })]);
+
+    assert_eq!(state.last_login(), 1_700_000_000);
+}
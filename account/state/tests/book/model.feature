@@ -10,3 +10,13 @@ Feature: Number feature
         When i try to remove 22 reputation
         Then reputation is 20
         Then i got an error
+
+    Scenario: the reputation removed is given by its event
+        Given the event {"ReputationAdded": 20}
+        When the command {"RemoveReputation": 5}
+        Then the events are [{"ReputationRemoved": 5}]
+
+    Scenario: a removal above the reputation is rejected
+        Given the event {"ReputationAdded": 20}
+        When the command {"RemoveReputation": 22}
+        Then the command is rejected with "cannot remove 22 from 20"
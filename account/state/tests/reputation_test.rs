@@ -0,0 +1,57 @@
+use account_shared::AccountCommand::{AddReputation, RemoveReputation};
+use account_state::error::AccountError;
+use account_state::event::AccountEvent::{ReputationAdded, ReputationRemoved};
+use account_state::state::AccountState;
+use state::testing::given;
+use state_repository::error::CommandError;
+use state_repository::model_key::ModelKey;
+use state_repository::snapshot::InMemorySnapshotStore;
+use state_repository::storage::InMemoryStorage;
+use state_repository::StateRepository;
+use uuid::Uuid;
+
+#[test]
+fn add_case() {
+    let state = given::<AccountState>([ReputationAdded(20)])
+        .when(AddReputation(22))
+        .then_events([ReputationAdded(22)]);
+
+    assert_eq!(state.reputation(), 42);
+}
+
+#[test]
+fn remove_case() {
+    let state = given::<AccountState>([ReputationAdded(20), ReputationRemoved(5)])
+        .when(RemoveReputation(15))
+        .then_events([ReputationRemoved(15)]);
+
+    assert_eq!(state.reputation(), 0);
+}
+
+#[test]
+fn remove_too_much_case() {
+    let state = given::<AccountState>([ReputationAdded(20)])
+        .when(RemoveReputation(22))
+        .then_error("cannot remove 22 from 20");
+
+    assert_eq!(state.reputation(), 20);
+}
+
+#[tokio::test]
+async fn typed_rejection_case() {
+    let repo = StateRepository::new(InMemoryStorage::default(), InMemorySnapshotStore::default());
+    let key = ModelKey::new("account".to_string(), Uuid::new_v4().to_string());
+
+    repo.add_command::<AccountState>(&key, AddReputation(20), None)
+        .await
+        .unwrap();
+
+    let rejected = repo
+        .add_command::<AccountState>(&key, RemoveReputation(22), None)
+        .await;
+
+    assert!(matches!(
+        rejected,
+        Err(CommandError::Rejected(AccountError::WrongQuantity(_)))
+    ));
+}
@@ -0,0 +1,320 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, ExprLit, Fields, Lit, LitStr,
+    Result, Variant,
+};
+
+/// Implement `state::Command`, the name of a variant being its identifier unless given
+/// by `#[command(name = "...")]`, a string or a constant.
+///
+/// Two variants with the same name do not compile:
+///
+/// ```compile_fail
+/// use serde::{Deserialize, Serialize};
+/// use state::Command;
+///
+/// #[derive(Clone, Debug, Deserialize, Serialize, Command)]
+/// enum GoldCommand {
+///     Pay(u32),
+///     #[command(name = "Pay")]
+///     Refund(u32),
+/// }
+/// ```
+#[proc_macro_derive(Command, attributes(command))]
+pub fn derive_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_command(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Implement `state::Event`, the name of a variant being its identifier unless given by
+/// `#[event(name = "...")]`, a string or a constant.
+///
+/// `#[event(public)]` on a variant, or on the type for all of them, makes its events
+/// shared between states. `#[event(forward)]` on a variant of a single field takes the
+/// name, the visibility and the version of the event it holds. A struct is a single event,
+/// named by `#[event(name = "...")]` on the type.
+///
+/// `#[event(version = N)]` on a variant, or on the type for all of them, gives the version
+/// of its serialized events, 1 unless given.
+///
+/// Two variants with the same name do not compile:
+///
+/// ```compile_fail
+/// use serde::{Deserialize, Serialize};
+/// use state::Event;
+///
+/// const PAID: &str = "paid";
+///
+/// #[derive(Clone, Debug, Deserialize, Serialize, Event)]
+/// enum GoldEvent {
+///     #[event(name = PAID)]
+///     Paid(u32),
+///     #[event(name = "paid")]
+///     Refunded(u32),
+/// }
+/// ```
+#[proc_macro_derive(Event, attributes(event))]
+pub fn derive_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_event(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Content of a `#[command(..)]` or `#[event(..)]` attribute.
+#[derive(Default)]
+struct Options {
+    name: Option<Expr>,
+    version: Option<Expr>,
+    public: bool,
+    forward: bool,
+}
+
+impl Options {
+    fn parse(attrs: &[Attribute], attr: &str, allowed: &[&str]) -> Result<Self> {
+        let mut options = Options::default();
+
+        for attribute in attrs.iter().filter(|a| a.path().is_ident(attr)) {
+            attribute.parse_nested_meta(|meta| {
+                let supported = allowed.iter().any(|allowed| meta.path.is_ident(allowed));
+
+                if supported && meta.path.is_ident("name") {
+                    options.name = Some(meta.value()?.parse()?);
+                } else if supported && meta.path.is_ident("version") {
+                    options.version = Some(meta.value()?.parse()?);
+                } else if supported && meta.path.is_ident("public") {
+                    options.public = true;
+                } else if supported && meta.path.is_ident("forward") {
+                    options.forward = true;
+                } else {
+                    return Err(meta.error(format!(
+                        "unsupported {attr} attribute, expected one of {}",
+                        allowed.join(", ")
+                    )));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// Names given so far, checked for duplicates.
+#[derive(Default)]
+struct Names {
+    names: Vec<Expr>,
+    literals: Vec<String>,
+}
+
+impl Names {
+    /// Name of a variant, its identifier unless given.
+    fn push(&mut self, name: Option<Expr>, variant: &Variant) -> Result<Expr> {
+        let name = name.unwrap_or_else(|| {
+            let ident = LitStr::new(&variant.ident.to_string(), variant.ident.span());
+            syn::parse_quote!(#ident)
+        });
+
+        // literals are checked here to point at the duplicate, constants at compile time
+        if let Expr::Lit(ExprLit {
+            lit: Lit::Str(literal),
+            ..
+        }) = &name
+        {
+            let value = literal.value();
+            if self.literals.contains(&value) {
+                return Err(Error::new(
+                    name.span(),
+                    format!("name {value:?} is already given to an other variant"),
+                ));
+            }
+            self.literals.push(value);
+        }
+
+        self.names.push(name.clone());
+        Ok(name)
+    }
+
+    fn check(&self, input: &DeriveInput, kind: &str) -> TokenStream2 {
+        if self.names.len() < 2 {
+            return TokenStream2::new();
+        }
+
+        let names = &self.names;
+        let message = format!("two variants of {} have the same {kind} name", input.ident);
+
+        quote! {
+            const _: () = ::core::assert!(
+                !::state::__private::has_duplicates(&[#(#names),*]),
+                #message
+            );
+        }
+    }
+}
+
+fn variants<'a>(input: &'a DeriveInput, derive: &str) -> Result<Vec<&'a Variant>> {
+    match &input.data {
+        Data::Enum(data) => Ok(data.variants.iter().collect()),
+        _ => Err(Error::new(
+            input.ident.span(),
+            format!("{derive} can only be derived for an enum"),
+        )),
+    }
+}
+
+fn expand_command(input: DeriveInput) -> Result<TokenStream2> {
+    let mut names = Names::default();
+    let mut arms = Vec::new();
+
+    for variant in variants(&input, "Command")? {
+        let options = Options::parse(&variant.attrs, "command", &["name"])?;
+        let name = names.push(options.name, variant)?;
+        let ident = &variant.ident;
+
+        arms.push(quote! { Self::#ident { .. } => #name, });
+    }
+
+    let check = names.check(&input, "command");
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::state::Command for #ident #ty_generics #where_clause {
+            fn command_name(&self) -> ::state::CommandName {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+
+        #check
+    })
+}
+
+fn expand_event(input: DeriveInput) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let (event_name, is_state_specific, event_version, check) = match &input.data {
+        Data::Struct(_) => {
+            let options = Options::parse(&input.attrs, "event", &["name", "public", "version"])?;
+            let name = options.name.unwrap_or_else(|| {
+                let ident = LitStr::new(&ident.to_string(), ident.span());
+                syn::parse_quote!(#ident)
+            });
+            let state_specific = !options.public;
+            let version = version(options.version);
+
+            (
+                quote! { #name },
+                quote! { #state_specific },
+                quote! { #version },
+                TokenStream2::new(),
+            )
+        }
+        _ => {
+            let container = Options::parse(&input.attrs, "event", &["public", "version"])?;
+
+            let mut names = Names::default();
+            let mut name_arms = Vec::new();
+            let mut specific_arms = Vec::new();
+            let mut version_arms = Vec::new();
+
+            for variant in variants(&input, "Event")? {
+                let options = Options::parse(
+                    &variant.attrs,
+                    "event",
+                    &["name", "public", "forward", "version"],
+                )?;
+                let variant_ident = &variant.ident;
+
+                if options.forward {
+                    let given = options.name.is_some() || options.version.is_some();
+                    let pattern = forward_pattern(variant, given)?;
+
+                    name_arms.push(quote! {
+                        #pattern => ::state::Event::event_name(inner),
+                    });
+                    specific_arms.push(quote! {
+                        #pattern => ::state::Event::is_state_specific(inner),
+                    });
+                    version_arms.push(quote! {
+                        #pattern => ::state::Event::event_version(inner),
+                    });
+                    continue;
+                }
+
+                let name = names.push(options.name, variant)?;
+                let state_specific = !(options.public || container.public);
+                let version = version(options.version.or_else(|| container.version.clone()));
+
+                name_arms.push(quote! { Self::#variant_ident { .. } => #name, });
+                specific_arms.push(quote! { Self::#variant_ident { .. } => #state_specific, });
+                version_arms.push(quote! { Self::#variant_ident { .. } => #version, });
+            }
+
+            (
+                quote! { match self { #(#name_arms)* } },
+                quote! { match self { #(#specific_arms)* } },
+                quote! { match self { #(#version_arms)* } },
+                names.check(&input, "event"),
+            )
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::state::Event for #ident #ty_generics #where_clause {
+            fn event_name(&self) -> ::state::EventName {
+                #event_name
+            }
+
+            fn is_state_specific(&self) -> bool {
+                #is_state_specific
+            }
+
+            fn event_version(&self) -> u32 {
+                #event_version
+            }
+        }
+
+        #check
+    })
+}
+
+/// Version of an event, 1 unless given.
+fn version(version: Option<Expr>) -> Expr {
+    version.unwrap_or_else(|| syn::parse_quote!(1))
+}
+
+/// Pattern binding `inner` to the single field of a forwarded variant.
+fn forward_pattern(variant: &Variant, given: bool) -> Result<TokenStream2> {
+    let ident = &variant.ident;
+    let inner = format_ident!("inner");
+
+    if given {
+        return Err(Error::new(
+            variant.span(),
+            "a forwarded variant takes the name and the version of the event it holds",
+        ));
+    }
+
+    match &variant.fields {
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Ok(quote! { Self::#ident(#inner) }),
+        Fields::Named(fields) if fields.named.len() == 1 => {
+            let field = &fields.named[0].ident;
+            Ok(quote! { Self::#ident { #field: #inner } })
+        }
+        _ => Err(Error::new(
+            variant.span(),
+            "only a variant of a single field can be forwarded",
+        )),
+    }
+}
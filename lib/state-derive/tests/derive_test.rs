@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use state::{Command, Event};
+
+const PAID: &str = "paid";
+const REFUNDED_VERSION: u32 = 3;
+
+#[derive(Clone, Debug, Deserialize, Serialize, Command)]
+enum GoldCommand {
+    Pay(u32),
+    #[command(name = "refund")]
+    Refund {
+        amount: u32,
+    },
+    Close,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Event)]
+#[event(name = "payment_done", public, version = 2)]
+struct PaymentDone {
+    amount: u32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Event)]
+enum GoldEvent {
+    #[event(name = PAID)]
+    Paid(u32),
+    #[event(version = REFUNDED_VERSION)]
+    Refunded { amount: u32 },
+    #[event(public)]
+    Closed,
+    #[event(forward)]
+    Public(PaymentDone),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Event)]
+#[event(public, version = 2)]
+enum PublicEvent {
+    Asked,
+    #[event(version = 4)]
+    Refused,
+    #[event(name = "answered")]
+    Answered(u32),
+}
+
+#[test]
+fn command_case() {
+    assert_eq!(GoldCommand::Pay(3).command_name(), "Pay");
+    assert_eq!(GoldCommand::Refund { amount: 3 }.command_name(), "refund");
+    assert_eq!(GoldCommand::Close.command_name(), "Close");
+}
+
+#[test]
+fn event_case() {
+    assert_eq!(GoldEvent::Paid(3).event_name(), PAID);
+    assert!(GoldEvent::Paid(3).is_state_specific());
+
+    let refunded = GoldEvent::Refunded { amount: 3 };
+    assert_eq!(refunded.event_name(), "Refunded");
+    assert!(refunded.is_state_specific());
+
+    assert_eq!(GoldEvent::Closed.event_name(), "Closed");
+    assert!(!GoldEvent::Closed.is_state_specific());
+
+    // a forwarded variant is the event it holds
+    let public = GoldEvent::Public(PaymentDone { amount: 3 });
+    assert_eq!(public.event_name(), "payment_done");
+    assert!(!public.is_state_specific());
+}
+
+#[test]
+fn public_case() {
+    assert_eq!(PublicEvent::Asked.event_name(), "Asked");
+    assert!(!PublicEvent::Asked.is_state_specific());
+    assert_eq!(PublicEvent::Answered(1).event_name(), "answered");
+    assert!(!PublicEvent::Answered(1).is_state_specific());
+}
+
+#[test]
+fn version_case() {
+    assert_eq!(GoldEvent::Paid(3).event_version(), 1);
+    assert_eq!(GoldEvent::Refunded { amount: 3 }.event_version(), 3);
+    assert_eq!(
+        GoldEvent::Public(PaymentDone { amount: 3 }).event_version(),
+        2
+    );
+
+    // the version of the type is the one of its variants unless given
+    assert_eq!(PublicEvent::Asked.event_version(), 2);
+    assert_eq!(PublicEvent::Refused.event_version(), 4);
+    assert_eq!(PaymentDone { amount: 3 }.event_version(), 2);
+}
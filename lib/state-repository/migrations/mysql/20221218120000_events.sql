@@ -0,0 +1,20 @@
+
+CREATE TABLE `events` (
+  `position` bigint NOT NULL AUTO_INCREMENT,
+  `stream_id` varchar(255) NOT NULL,
+  `revision` bigint NOT NULL,
+  `event_id` char(36) NOT NULL,
+  `event_type` varchar(255) NOT NULL,
+  `data` longtext NOT NULL,
+  `metadata` text NOT NULL,
+  `correlation_id` char(36) NULL,
+  `created` bigint NULL,
+  `idempotency_key` varchar(255) NULL,
+  PRIMARY KEY (`position`)
+) ENGINE='InnoDB' COLLATE 'utf8mb4_general_ci';
+
+ALTER TABLE `events`
+ADD UNIQUE `stream_revision` (`stream_id`, `revision`),
+ADD INDEX `event_type` (`event_type`, `position`),
+ADD INDEX `correlation` (`correlation_id`, `position`),
+ADD INDEX `idempotency` (`stream_id`, `idempotency_key`);
@@ -0,0 +1,7 @@
+
+CREATE TABLE `checkpoints` (
+  `consumer` varchar(255) NOT NULL,
+  `event_type` varchar(255) NOT NULL,
+  `position` bigint NOT NULL,
+  PRIMARY KEY (`consumer`, `event_type`)
+) ENGINE='InnoDB' COLLATE 'utf8mb4_general_ci';
@@ -0,0 +1,18 @@
+CREATE TABLE `scheduled_commands` (
+  `id` varchar(36) NOT NULL,
+  `due` bigint NOT NULL,
+  `stream_name` varchar(255) NOT NULL,
+  `stream_id` varchar(255) NOT NULL,
+  `state_name` varchar(255) NOT NULL,
+  `command` text NOT NULL,
+  `metadata` text NULL,
+  `metadata_id` varchar(36) NULL,
+  PRIMARY KEY (`id`),
+  KEY `scheduled_commands_due` (`due`)
+) ENGINE='InnoDB' COLLATE 'utf8mb4_general_ci';
+
+CREATE TABLE `unscheduled_actions` (
+  `id` varchar(36) NOT NULL,
+  `due` bigint NULL,
+  PRIMARY KEY (`id`)
+) ENGINE='InnoDB' COLLATE 'utf8mb4_general_ci';
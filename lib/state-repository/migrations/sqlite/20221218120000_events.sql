@@ -0,0 +1,18 @@
+
+CREATE TABLE `events` (
+  `position` INTEGER PRIMARY KEY AUTOINCREMENT,
+  `stream_id` varchar(255) NOT NULL,
+  `revision` bigint NOT NULL,
+  `event_id` char(36) NOT NULL,
+  `event_type` varchar(255) NOT NULL,
+  `data` text NOT NULL,
+  `metadata` text NOT NULL,
+  `correlation_id` char(36) NULL,
+  `created` bigint NULL,
+  `idempotency_key` varchar(255) NULL,
+  UNIQUE (`stream_id`, `revision`)
+);
+
+CREATE INDEX `event_type` ON `events` (`event_type`, `position`);
+CREATE INDEX `correlation` ON `events` (`correlation_id`, `position`);
+CREATE INDEX `idempotency` ON `events` (`stream_id`, `idempotency_key`);
@@ -0,0 +1,131 @@
+use crate::error::RepositoryError;
+use crate::storage::{json_or_text, StoredEvent};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Command or event of a correlation, with the commands and events it caused.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CausationNode {
+    id: Uuid,
+    stream_id: String,
+    revision: u64,
+    event_type: String,
+    is_event: bool,
+    created: Option<u64>,
+    data: Value,
+    children: Vec<CausationNode>,
+}
+
+impl CausationNode {
+    fn new(event: &StoredEvent, is_event: bool) -> Self {
+        Self {
+            id: event.id(),
+            stream_id: event.stream_id().to_string(),
+            revision: event.revision(),
+            event_type: event.event_type().to_string(),
+            is_event,
+            created: event
+                .created()
+                .and_then(|created| created.duration_since(UNIX_EPOCH).ok())
+                .map(|created| created.as_millis() as u64),
+            data: json_or_text(event.data()),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+    pub fn stream_id(&self) -> &str {
+        &self.stream_id
+    }
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+    /// Type of the command or event, like `cmd.test-gold.Pay` or `evt.payment_asked`.
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+    pub fn is_event(&self) -> bool {
+        self.is_event
+    }
+    /// When the command or event was stored, unknown for the ones stored before the
+    /// storage recorded it.
+    pub fn created(&self) -> Option<SystemTime> {
+        self.created
+            .map(|created| UNIX_EPOCH + Duration::from_millis(created))
+    }
+    pub fn data(&self) -> &Value {
+        &self.data
+    }
+    /// Commands and events caused by this one, in the order they were stored.
+    pub fn children(&self) -> &[CausationNode] {
+        &self.children
+    }
+}
+
+/// Rebuild the causation trees of the commands and events of a correlation, read in
+/// the order they were stored.
+///
+/// The roots are the ones whose cause is not part of the correlation, usually the
+/// single command starting it.
+pub(crate) fn causation_tree(
+    events: Vec<StoredEvent>,
+) -> Result<Vec<CausationNode>, RepositoryError> {
+    let ids: HashSet<Uuid> = events.iter().map(|e| e.id()).collect();
+
+    let mut roots = Vec::new();
+    let mut nodes = HashMap::new();
+    let mut children: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+
+    for event in &events {
+        let metadata = event.metadata()?;
+        let causation_id = metadata.causation_id();
+
+        if causation_id == event.id() || !ids.contains(&causation_id) {
+            roots.push(event.id());
+        } else {
+            children.entry(causation_id).or_default().push(event.id());
+        }
+
+        nodes.insert(event.id(), CausationNode::new(event, metadata.is_event()));
+    }
+
+    Ok(roots
+        .into_iter()
+        .filter_map(|id| attach(id, &mut nodes, &mut children))
+        .collect())
+}
+
+/// Take the node `id` out of `nodes` with the ones it caused, keeping the nodes whose
+/// children are still being taken on a stack, as a flow may be long.
+fn attach(
+    id: Uuid,
+    nodes: &mut HashMap<Uuid, CausationNode>,
+    children: &mut HashMap<Uuid, Vec<Uuid>>,
+) -> Option<CausationNode> {
+    let root = nodes.remove(&id)?;
+    let mut stack = vec![(root, children.remove(&id).unwrap_or_default().into_iter())];
+
+    loop {
+        match stack.last_mut()?.1.next() {
+            Some(child_id) => {
+                if let Some(child) = nodes.remove(&child_id) {
+                    let caused = children.remove(&child_id).unwrap_or_default();
+                    stack.push((child, caused.into_iter()));
+                }
+            }
+            None => {
+                let (node, _) = stack.pop()?;
+
+                match stack.last_mut() {
+                    Some((parent, _)) => parent.children.push(node),
+                    None => return Some(node),
+                }
+            }
+        }
+    }
+}
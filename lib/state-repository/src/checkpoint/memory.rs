@@ -0,0 +1,47 @@
+use crate::checkpoint::CheckpointStore;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Checkpoints living in the process memory, shared between its clones.
+#[derive(Clone, Default)]
+pub struct InMemoryCheckpointStore {
+    positions: Arc<Mutex<HashMap<(String, String), u64>>>,
+}
+
+#[async_trait]
+impl CheckpointStore for InMemoryCheckpointStore {
+    async fn load(&self, consumer: &str, event_type: &str) -> Result<Option<u64>> {
+        let positions = self
+            .positions
+            .lock()
+            .map_err(|e| anyhow!("lock memory checkpoints : {e}"))?;
+
+        Ok(positions
+            .get(&(consumer.to_string(), event_type.to_string()))
+            .copied())
+    }
+
+    async fn save(&self, consumer: &str, event_type: &str, position: u64) -> Result<()> {
+        let mut positions = self
+            .positions
+            .lock()
+            .map_err(|e| anyhow!("lock memory checkpoints : {e}"))?;
+
+        positions.insert((consumer.to_string(), event_type.to_string()), position);
+
+        Ok(())
+    }
+
+    async fn clear(&self, consumer: &str) -> Result<()> {
+        let mut positions = self
+            .positions
+            .lock()
+            .map_err(|e| anyhow!("lock memory checkpoints : {e}"))?;
+
+        positions.retain(|(c, _), _| c != consumer);
+
+        Ok(())
+    }
+}
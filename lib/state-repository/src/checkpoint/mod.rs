@@ -0,0 +1,23 @@
+mod memory;
+mod redis_store;
+mod sql;
+
+pub use memory::InMemoryCheckpointStore;
+pub use redis_store::RedisCheckpointStore;
+pub use sql::SqlCheckpointStore;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// Store of the positions reached by the consumers of event types, so that they
+/// resume where they stopped.
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    /// Position of the last event of `event_type` handled by `consumer`.
+    async fn load(&self, consumer: &str, event_type: &str) -> Result<Option<u64>>;
+
+    async fn save(&self, consumer: &str, event_type: &str, position: u64) -> Result<()>;
+
+    /// Forget every position of `consumer`.
+    async fn clear(&self, consumer: &str) -> Result<()>;
+}
@@ -0,0 +1,53 @@
+use crate::checkpoint::CheckpointStore;
+use crate::redis_connection::RedisConnection;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use redis::{AsyncCommands, Client};
+
+/// Checkpoints kept in a `checkpoint.{consumer}` Redis hash by event type.
+#[derive(Clone)]
+pub struct RedisCheckpointStore {
+    connection: RedisConnection,
+}
+
+impl RedisCheckpointStore {
+    pub fn new(client: Client) -> Self {
+        Self {
+            connection: RedisConnection::new(client),
+        }
+    }
+}
+
+fn hash_key(consumer: &str) -> String {
+    format!("checkpoint.{consumer}")
+}
+
+#[async_trait]
+impl CheckpointStore for RedisCheckpointStore {
+    async fn load(&self, consumer: &str, event_type: &str) -> Result<Option<u64>> {
+        self.connection
+            .get()
+            .await?
+            .hget(hash_key(consumer), event_type)
+            .await
+            .context("get checkpoint")
+    }
+
+    async fn save(&self, consumer: &str, event_type: &str, position: u64) -> Result<()> {
+        self.connection
+            .get()
+            .await?
+            .hset(hash_key(consumer), event_type, position)
+            .await
+            .context("set checkpoint")
+    }
+
+    async fn clear(&self, consumer: &str) -> Result<()> {
+        self.connection
+            .get()
+            .await?
+            .del(hash_key(consumer))
+            .await
+            .context("clear checkpoints")
+    }
+}
@@ -0,0 +1,87 @@
+use crate::checkpoint::CheckpointStore;
+use crate::storage::SqlStorage;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use sqlx::Row;
+
+/// Checkpoints kept in the `checkpoints` table next to the events, the table being
+/// created by `SqlStorage::migrate`.
+#[derive(Clone)]
+pub struct SqlCheckpointStore {
+    storage: SqlStorage,
+}
+
+impl SqlCheckpointStore {
+    pub fn new(storage: SqlStorage) -> Self {
+        Self { storage }
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for SqlCheckpointStore {
+    async fn load(&self, consumer: &str, event_type: &str) -> Result<Option<u64>> {
+        let query = self
+            .storage
+            .query("SELECT position FROM checkpoints WHERE consumer = ? AND event_type = ?");
+
+        let row = sqlx::query(&query)
+            .bind(consumer)
+            .bind(event_type)
+            .fetch_optional(self.storage.pool())
+            .await
+            .context("read checkpoint")?;
+
+        row.map(|row| {
+            let position: i64 = row.try_get("position").context("decode checkpoint")?;
+            Ok(position as u64)
+        })
+        .transpose()
+    }
+
+    async fn save(&self, consumer: &str, event_type: &str, position: u64) -> Result<()> {
+        // delete then insert, as the upsert syntax differs between databases
+        let mut transaction = self
+            .storage
+            .pool()
+            .begin()
+            .await
+            .context("begin checkpoint")?;
+
+        let query = self
+            .storage
+            .query("DELETE FROM checkpoints WHERE consumer = ? AND event_type = ?");
+        sqlx::query(&query)
+            .bind(consumer)
+            .bind(event_type)
+            .execute(&mut transaction)
+            .await
+            .context("delete checkpoint")?;
+
+        let query = self
+            .storage
+            .query("INSERT INTO checkpoints (consumer, event_type, position) VALUES (?, ?, ?)");
+        sqlx::query(&query)
+            .bind(consumer)
+            .bind(event_type)
+            .bind(position as i64)
+            .execute(&mut transaction)
+            .await
+            .context("insert checkpoint")?;
+
+        transaction.commit().await.context("commit checkpoint")
+    }
+
+    async fn clear(&self, consumer: &str) -> Result<()> {
+        let query = self
+            .storage
+            .query("DELETE FROM checkpoints WHERE consumer = ?");
+
+        sqlx::query(&query)
+            .bind(consumer)
+            .execute(self.storage.pool())
+            .await
+            .context("clear checkpoints")?;
+
+        Ok(())
+    }
+}
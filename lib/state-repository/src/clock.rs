@@ -0,0 +1 @@
+pub use state::clock::{Clock, ManualClock, SystemClock};
@@ -0,0 +1,589 @@
+use crate::checkpoint::CheckpointStore;
+use crate::dead_letter::{self, DeadLetter, Resolution};
+use crate::error::RepositoryError;
+use crate::model_key::ModelKey;
+use crate::retry::RetryPolicy;
+use crate::storage::{EventSubscription, StoredEvent, SubscriptionStart};
+use crate::StateRepository;
+use anyhow::anyhow;
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+use serde::Serialize;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::{sleep, Duration, Instant};
+use uuid::Uuid;
+
+pub type ConsumerHandle = JoinHandle<Result<(), RepositoryError>>;
+
+const MAX_IN_FLIGHT: usize = 16;
+
+/// Positions received by a consumer whose events are handled concurrently. The
+/// checkpoint only moves past an event once it and every event before it are handled.
+#[derive(Default)]
+struct Acknowledgements {
+    received: BTreeMap<u64, bool>,
+}
+
+impl Acknowledgements {
+    fn receive(&mut self, position: u64) {
+        self.received.insert(position, false);
+    }
+
+    /// Mark a position as handled, returning the new checkpoint if it moved.
+    fn acknowledge(&mut self, position: u64) -> Option<u64> {
+        self.received.insert(position, true);
+
+        let mut checkpoint = None;
+
+        while let Some(entry) = self.received.first_entry() {
+            if !*entry.get() {
+                break;
+            }
+            checkpoint = Some(entry.remove_entry().0);
+        }
+
+        checkpoint
+    }
+}
+
+/// Events received by a consumer and not handled yet, by stream. A single event of a
+/// stream is handled at a time, the following ones waiting for it in their order.
+#[derive(Default)]
+struct Streams {
+    waiting: HashMap<String, VecDeque<StoredEvent>>,
+    received: usize,
+}
+
+impl Streams {
+    /// Receive an event, returning it when no event of its stream is being handled.
+    fn receive(&mut self, event: StoredEvent) -> Option<StoredEvent> {
+        self.received += 1;
+
+        match self.waiting.get_mut(event.stream_id()) {
+            Some(waiting) => {
+                waiting.push_back(event);
+                None
+            }
+            None => {
+                self.waiting
+                    .insert(event.stream_id().to_string(), VecDeque::new());
+                Some(event)
+            }
+        }
+    }
+
+    /// Once an event is handled, the next event of its stream to handle.
+    fn handled(&mut self, event: &StoredEvent) -> Option<StoredEvent> {
+        self.received -= 1;
+
+        let waiting = self.waiting.get_mut(event.stream_id())?;
+        let next = waiting.pop_front();
+
+        if next.is_none() {
+            self.waiting.remove(event.stream_id());
+        }
+
+        next
+    }
+
+    /// Events received and not handled yet, the waiting ones included.
+    fn len(&self) -> usize {
+        self.received
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum ProcessorState {
+    Running,
+    /// Waiting to subscribe again after a transient error.
+    Restarting,
+    /// Given up after too many restarts, or ended with its subscription.
+    Stopped,
+}
+
+/// What a supervised consumer of an event type has been doing.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProcessorStatus {
+    consumer: String,
+    event_type: String,
+    state: ProcessorState,
+    checkpoint: Option<u64>,
+    handled: u64,
+    dead_letters: u64,
+    restarts: u64,
+    last_error: Option<String>,
+}
+
+impl ProcessorStatus {
+    fn new(consumer: &str, event_type: &str) -> Self {
+        Self {
+            consumer: consumer.to_string(),
+            event_type: event_type.to_string(),
+            state: ProcessorState::Running,
+            checkpoint: None,
+            handled: 0,
+            dead_letters: 0,
+            restarts: 0,
+            last_error: None,
+        }
+    }
+
+    pub fn consumer(&self) -> &str {
+        &self.consumer
+    }
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+    pub fn state(&self) -> ProcessorState {
+        self.state
+    }
+    /// Position of the last event acknowledged.
+    pub fn checkpoint(&self) -> Option<u64> {
+        self.checkpoint
+    }
+    /// Events handled since the processor started, the dead letters included.
+    pub fn handled(&self) -> u64 {
+        self.handled
+    }
+    pub fn dead_letters(&self) -> u64 {
+        self.dead_letters
+    }
+    pub fn restarts(&self) -> u64 {
+        self.restarts
+    }
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Move the checkpoint of a task following events without the supervisor, as a
+    /// projection.
+    pub(crate) fn acknowledge(&mut self, checkpoint: Option<u64>) {
+        if checkpoint.is_some() {
+            self.checkpoint = checkpoint;
+        }
+    }
+
+    /// Count what a task handled besides events, as the commands of the scheduler.
+    pub(crate) fn record(
+        &mut self,
+        handled: usize,
+        dead_letters: usize,
+        error: Option<&RepositoryError>,
+    ) {
+        self.handled += handled as u64;
+        self.dead_letters += dead_letters as u64;
+        if let Some(error) = error {
+            self.last_error = Some(error.to_string());
+        }
+    }
+}
+
+type Statuses = BTreeMap<(String, String), ProcessorStatus>;
+
+type Handler = dyn Fn(StateRepository, StoredEvent) -> BoxFuture<'static, Result<(), RepositoryError>>
+    + Send
+    + Sync;
+
+type Handlers = HashMap<(String, String), Arc<Handler>>;
+
+/// Run the consumers of event types from their checkpoints, keeping their status.
+///
+/// A consumer handles at most `max_in_flight` events at once, the events of a stream one
+/// after the other in their order. When it restarts, the handlers still running are
+/// aborted, their events being received again.
+///
+/// An event failing with a transient error restarts the subscription of its consumer
+/// after a backoff, from the last checkpoint. An event failing otherwise, or whose
+/// handling panics, is sent to the dead-letter stream with the error then acknowledged,
+/// so that the following events are still handled. Dead letters can be retried by the
+/// handler of their consumer, or discarded.
+#[derive(Clone)]
+pub struct Supervisor {
+    repo: StateRepository,
+    checkpoints: Arc<dyn CheckpointStore>,
+    restart_policy: RetryPolicy,
+    max_in_flight: usize,
+    statuses: Arc<Mutex<Statuses>>,
+    handlers: Arc<Mutex<Handlers>>,
+}
+
+impl Supervisor {
+    pub fn new<C>(repo: StateRepository, checkpoints: C) -> Self
+    where
+        C: CheckpointStore + 'static,
+    {
+        Self {
+            repo,
+            checkpoints: Arc::new(checkpoints),
+            restart_policy: RetryPolicy::default()
+                .max_attempts(u32::MAX)
+                .max_backoff(Duration::from_secs(30))
+                .deadline(None),
+            max_in_flight: MAX_IN_FLIGHT,
+            statuses: Arc::default(),
+            handlers: Arc::default(),
+        }
+    }
+
+    /// Backoff between the restarts of a consumer, which stops once the policy gives
+    /// up. Restarts are counted again from the first after an event is handled.
+    pub fn with_restart_policy(mut self, restart_policy: RetryPolicy) -> Self {
+        self.restart_policy = restart_policy;
+        self
+    }
+
+    /// Events a consumer receives before they are handled, 16 by default, the events of
+    /// different streams being handled at once.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    pub fn repo(&self) -> &StateRepository {
+        &self.repo
+    }
+
+    /// Status of every consumer started, by consumer then event type.
+    pub fn statuses(&self) -> Vec<ProcessorStatus> {
+        self.statuses.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn status(&self, consumer: &str, event_type: &str) -> Option<ProcessorStatus> {
+        self.statuses
+            .lock()
+            .unwrap()
+            .get(&(consumer.to_string(), event_type.to_string()))
+            .cloned()
+    }
+
+    /// Dead letters of a consumer not retried nor discarded yet, the oldest first.
+    pub async fn dead_letters(&self, consumer: &str) -> Result<Vec<DeadLetter>, RepositoryError> {
+        dead_letter::list(&self.repo, consumer).await
+    }
+
+    /// Dead letter of a consumer for an event, if not retried nor discarded yet.
+    pub async fn dead_letter(
+        &self,
+        consumer: &str,
+        event_id: Uuid,
+    ) -> Result<Option<DeadLetter>, RepositoryError> {
+        dead_letter::inspect(&self.repo, consumer, event_id).await
+    }
+
+    /// Handle the event of a dead letter again with its consumer, which must have been
+    /// started. Telling whether there was a dead letter, which is kept when it fails again.
+    pub async fn retry_dead_letter(
+        &self,
+        consumer: &str,
+        event_id: Uuid,
+    ) -> Result<bool, RepositoryError> {
+        let dead_letter = match self.dead_letter(consumer, event_id).await? {
+            Some(dead_letter) => dead_letter,
+            None => return Ok(false),
+        };
+
+        let handler = self
+            .handlers
+            .lock()
+            .unwrap()
+            .get(&(consumer.to_string(), dead_letter.event_type().to_string()))
+            .cloned()
+            .ok_or_else(|| {
+                RepositoryError::Processing(anyhow!(
+                    "{consumer} is not started for {}",
+                    dead_letter.event_type()
+                ))
+            })?;
+
+        let key = ModelKey::from(dead_letter.stream_id().to_string());
+        let event = self
+            .repo
+            .event_db
+            .read_events(&key, Some(dead_letter.revision()))
+            .await?
+            .into_iter()
+            .find(|event| event.id() == event_id)
+            .ok_or_else(|| {
+                RepositoryError::Processing(anyhow!("event {event_id} of the dead letter is gone"))
+            })?;
+
+        match tokio::spawn(handler(self.repo.clone(), event)).await {
+            Ok(handled) => handled?,
+            Err(e) => return Err(RepositoryError::Processing(anyhow!("{e}"))),
+        }
+
+        dead_letter::resolve(&self.repo, &dead_letter, Resolution::Retried).await
+    }
+
+    /// Give up on a dead letter, telling whether there was one.
+    pub async fn discard_dead_letter(
+        &self,
+        consumer: &str,
+        event_id: Uuid,
+    ) -> Result<bool, RepositoryError> {
+        match self.dead_letter(consumer, event_id).await? {
+            Some(dead_letter) => {
+                dead_letter::resolve(&self.repo, &dead_letter, Resolution::Discarded).await
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub(crate) fn update<F>(&self, consumer: &str, event_type: &str, update: F)
+    where
+        F: FnOnce(&mut ProcessorStatus),
+    {
+        let mut statuses = self.statuses.lock().unwrap();
+        let status = statuses
+            .entry((consumer.to_string(), event_type.to_string()))
+            .or_insert_with(|| ProcessorStatus::new(consumer, event_type));
+        update(status);
+    }
+
+    async fn subscribe(
+        &self,
+        consumer: &str,
+        event_type: &str,
+    ) -> Result<EventSubscription, RepositoryError> {
+        let checkpoint = self
+            .checkpoints
+            .load(consumer, event_type)
+            .await
+            .map_err(RepositoryError::Checkpoint)?;
+
+        self.update(consumer, event_type, |status| {
+            status.checkpoint = checkpoint;
+        });
+
+        self.repo
+            .event_db
+            .subscribe_event_type(event_type, SubscriptionStart::from(checkpoint))
+            .await
+    }
+
+    /// Handle the events of `event_type` after the checkpoint of `consumer` in a
+    /// spawned task, each event in its own task, `max_in_flight` at most at once and a
+    /// single one by stream.
+    ///
+    /// The first subscription is opened before returning. Events are delivered at least
+    /// once: the ones handled but not yet acknowledged when the consumer stops or
+    /// restarts are handled again.
+    pub(crate) async fn consume<F, Fut>(
+        &self,
+        consumer: String,
+        event_type: String,
+        handle: F,
+    ) -> Result<ConsumerHandle, RepositoryError>
+    where
+        F: Fn(StateRepository, StoredEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), RepositoryError>> + Send + 'static,
+    {
+        let handle: Arc<Handler> = Arc::new(move |repo, event| Box::pin(handle(repo, event)));
+
+        self.handlers
+            .lock()
+            .unwrap()
+            .insert((consumer.clone(), event_type.clone()), handle.clone());
+
+        let mut subscription = Some(self.subscribe(&consumer, &event_type).await?);
+
+        let supervisor = self.clone();
+        let (name, kind) = (consumer.clone(), event_type.clone());
+
+        Ok(self.supervise(consumer, event_type, move || {
+            let supervisor = supervisor.clone();
+            let (consumer, event_type) = (name.clone(), kind.clone());
+            let handle = handle.clone();
+            let subscription = subscription.take();
+
+            async move {
+                let subscription = match subscription {
+                    Some(subscription) => subscription,
+                    None => supervisor.subscribe(&consumer, &event_type).await?,
+                };
+
+                supervisor
+                    .run(&consumer, &event_type, subscription, handle.as_ref())
+                    .await
+            }
+        }))
+    }
+
+    /// Run `task` in a spawned task until it ends, starting it again after a backoff
+    /// when it fails, its status being kept under `consumer` and `event_type`.
+    ///
+    /// The task should count what it handles in its status, so that restarts are
+    /// counted again from the first once it progresses.
+    pub(crate) fn supervise<F, Fut>(
+        &self,
+        consumer: String,
+        event_type: String,
+        mut task: F,
+    ) -> ConsumerHandle
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), RepositoryError>> + Send + 'static,
+    {
+        self.update(&consumer, &event_type, |status| {
+            status.state = ProcessorState::Running;
+        });
+
+        let supervisor = self.clone();
+
+        tokio::spawn(async move {
+            let mut attempt = 0;
+            let mut failing_since = Instant::now();
+
+            loop {
+                let handled = supervisor
+                    .status(&consumer, &event_type)
+                    .map(|status| status.handled);
+
+                let error = match task().await {
+                    Ok(()) => {
+                        supervisor.update(&consumer, &event_type, |status| {
+                            status.state = ProcessorState::Stopped;
+                        });
+                        return Ok(());
+                    }
+                    Err(e) => e,
+                };
+
+                let progressed = supervisor
+                    .status(&consumer, &event_type)
+                    .map(|status| status.handled)
+                    != handled;
+
+                if progressed {
+                    attempt = 0;
+                    failing_since = Instant::now();
+                }
+                attempt += 1;
+
+                let backoff = supervisor
+                    .restart_policy
+                    .next_backoff(attempt, failing_since);
+
+                supervisor.update(&consumer, &event_type, |status| {
+                    status.last_error = Some(error.to_string());
+                    status.state = match backoff {
+                        Some(_) => ProcessorState::Restarting,
+                        None => ProcessorState::Stopped,
+                    };
+                });
+
+                match backoff {
+                    Some(backoff) => sleep(backoff).await,
+                    None => return Err(error),
+                }
+
+                supervisor.update(&consumer, &event_type, |status| {
+                    status.restarts += 1;
+                    status.state = ProcessorState::Running;
+                });
+            }
+        })
+    }
+
+    /// Handle the events of a subscription until it ends, or until a transient error.
+    /// The handlers still running are aborted on return.
+    async fn run(
+        &self,
+        consumer: &str,
+        event_type: &str,
+        mut subscription: EventSubscription,
+        handle: &Handler,
+    ) -> Result<(), RepositoryError> {
+        let mut handling = JoinSet::new();
+        let mut acknowledgements = Acknowledgements::default();
+        let mut streams = Streams::default();
+
+        let spawn = |handling: &mut JoinSet<_>, event: StoredEvent| {
+            let handler = AssertUnwindSafe(handle(self.repo.clone(), event.clone()));
+
+            handling.spawn(async move {
+                let result = match handler.catch_unwind().await {
+                    Ok(result) => result,
+                    Err(panic) => Err(panicked(panic)),
+                };
+                (event, result)
+            });
+        };
+
+        loop {
+            tokio::select! {
+                event = subscription.next(), if streams.len() < self.max_in_flight => {
+                    let event = match event {
+                        Some(event) => event?,
+                        None => return Ok(()),
+                    };
+
+                    if let Some(position) = event.position() {
+                        acknowledgements.receive(position);
+                    }
+
+                    if let Some(event) = streams.receive(event) {
+                        spawn(&mut handling, event);
+                    }
+                }
+                Some(handled) = handling.join_next() => {
+                    let (event, result) = handled
+                        .map_err(|e| RepositoryError::Processing(anyhow!("{e}")))?;
+
+                    if let Err(error) = result {
+                        if error.is_transient() {
+                            return Err(error);
+                        }
+
+                        let dead_letter = DeadLetter::new(consumer, &event, &error);
+                        dead_letter::send(&self.repo, dead_letter, event.metadata().ok().as_ref())
+                            .await?;
+
+                        self.update(consumer, event_type, |status| {
+                            status.dead_letters += 1;
+                            status.last_error = Some(error.to_string());
+                        });
+                    }
+
+                    if let Some(next) = streams.handled(&event) {
+                        spawn(&mut handling, next);
+                    }
+
+                    let checkpoint = event
+                        .position()
+                        .and_then(|position| acknowledgements.acknowledge(position));
+
+                    if let Some(checkpoint) = checkpoint {
+                        self.checkpoints
+                            .save(consumer, event_type, checkpoint)
+                            .await
+                            .map_err(RepositoryError::Checkpoint)?;
+                    }
+
+                    self.update(consumer, event_type, |status| {
+                        status.handled += 1;
+                        if checkpoint.is_some() {
+                            status.checkpoint = checkpoint;
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Error of a handler which panicked, with its message when it has one.
+fn panicked(panic: Box<dyn Any + Send>) -> RepositoryError {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+
+    RepositoryError::Processing(anyhow!("handler panicked : {message}"))
+}
@@ -1,9 +1,14 @@
-use crate::metadata::Metadata;
+use crate::consumer::{ConsumerHandle, Supervisor};
+use crate::error::{CommandError, RepositoryError};
 use crate::model_key::ModelKey;
+use crate::options::CommandOptions;
+use crate::schedule::Scheduler;
+use crate::storage::StoredEvent;
 use crate::{StateRepository, EVENT_PREFIX};
 use async_trait::async_trait;
-use eventstore::{RecordedEvent, StreamPosition, SubscribeToStreamOptions};
 use state::{Event, EventName, State};
+use tokio::time::Duration;
+use uuid::Uuid;
 
 pub trait HasTarget {
     fn get_target(&self) -> ModelKey;
@@ -12,55 +17,61 @@ pub trait HasTarget {
 pub trait CrossData {
     type Question: Event + HasTarget;
     type Answer: Event + HasTarget;
+    /// Negative answer, by which the questioned state refuses the question.
+    type Refusal: Event + HasTarget;
 
     fn question_names() -> Vec<EventName>;
     fn answer_names() -> Vec<EventName>;
+    fn refusal_names() -> Vec<EventName>;
+
+    /// Time given to answer a question before the asking state receives a timeout
+    /// command, `None` to wait for the answer forever.
+    fn answer_deadline() -> Option<Duration> {
+        None
+    }
 }
 
+#[async_trait]
 pub trait CrossDataProcessor: State {
-    fn process(repo: StateRepository, event_name: EventName) {
-        let stream_name = format!("$et-{}.{}", EVENT_PREFIX, event_name);
-
-        println!("stream_name : {stream_name}");
-
-        tokio::spawn(async move {
-            let options = SubscribeToStreamOptions::default()
-                .start_from(StreamPosition::End)
-                .resolve_link_tos();
-
-            let mut stream = repo
-                .event_db
-                .clone()
-                .subscribe_to_stream(stream_name, &options)
-                .await;
-
-            loop {
-                let event_json = stream.next().await.unwrap();
-                let repo = repo.clone();
-
-                if let Some(recorded_event) = event_json.event {
-                    tokio::spawn(async move {
-                        let mut metadata: Metadata =
-                            serde_json::from_slice(recorded_event.custom_metadata.as_ref())
-                                .unwrap();
-                        metadata.set_id(Some(recorded_event.id));
-
-                        let repo = repo.clone();
-
-                        let local_key: ModelKey = recorded_event.stream_id.clone().into();
-
-                        let (cmd, target) = Self::resolve(recorded_event, local_key);
-
-                        repo.add_command::<Self>(&target, cmd, Some(&metadata))
-                            .await
-                            .unwrap();
-                    });
-                }
-            }
-        });
+    /// Resolve the events named `event_name` after the checkpoint of the state, so that
+    /// the ones emitted while no processor was running are still resolved.
+    ///
+    /// Events are decoded with the upcasters of the repository. An event is acknowledged
+    /// once its command is applied. The command carries the id
+    /// of the event as idempotency key, so an event delivered again is not applied twice.
+    /// An event which cannot be resolved or whose command is refused goes to the
+    /// dead-letter stream.
+    async fn process(
+        supervisor: &Supervisor,
+        event_name: EventName,
+    ) -> Result<ConsumerHandle, RepositoryError> {
+        let consumer = format!("{}.cross", Self::name_prefix());
+        let event_type = format!("{}.{}", EVENT_PREFIX, event_name);
+
+        supervisor
+            .consume(consumer, event_type, |repo, recorded_event| async move {
+                let metadata = recorded_event.metadata()?;
+                let options =
+                    CommandOptions::default().idempotency_key(recorded_event.id().to_string());
+
+                let local_key: ModelKey = recorded_event.key();
+
+                let (cmd, target) = Self::resolve(&repo, recorded_event, local_key)?;
+
+                repo.add_command_with_options::<Self>(&target, cmd, Some(&metadata), &options)
+                    .await
+                    .map_err(CommandError::into_processing)?;
+
+                Ok(())
+            })
+            .await
     }
 
-    fn resolve(e: RecordedEvent, local_key: ModelKey) -> (Self::Command, ModelKey);
+    fn resolve(
+        repo: &StateRepository,
+        e: StoredEvent,
+        local_key: ModelKey,
+    ) -> Result<(Self::Command, ModelKey), RepositoryError>;
 }
 
 #[async_trait]
@@ -70,17 +81,27 @@ where
 {
     fn resolve_question(event: C::Question, local_key: ModelKey) -> Self::Command;
 
-    async fn process_question(repo: StateRepository) {
+    async fn process_question(
+        supervisor: &Supervisor,
+    ) -> Result<Vec<ConsumerHandle>, RepositoryError> {
+        let mut handles = Vec::new();
+
         for event_name in C::question_names() {
-            Self::process(repo.clone(), event_name);
+            handles.push(Self::process(supervisor, event_name).await?);
         }
+
+        Ok(handles)
     }
 
-    fn resolve_helper(e: RecordedEvent, local_key: ModelKey) -> (Self::Command, ModelKey) {
-        let event = e.as_json::<C::Question>().unwrap();
+    fn resolve_helper(
+        repo: &StateRepository,
+        e: StoredEvent,
+        local_key: ModelKey,
+    ) -> Result<(Self::Command, ModelKey), RepositoryError> {
+        let event = repo.decode_event::<C::Question>(&e)?;
         let target = event.get_target();
         let cmd = Self::resolve_question(event, local_key);
-        (cmd, target)
+        Ok((cmd, target))
     }
 }
 
@@ -91,16 +112,143 @@ where
 {
     fn resolve_answer(event: C::Answer) -> Self::Command;
 
-    async fn process_query(repo: StateRepository) {
-        for event_name in C::answer_names() {
-            Self::process(repo.clone(), event_name);
+    fn resolve_refusal(event: C::Refusal) -> Self::Command;
+
+    /// Command for a question still without answer once `CrossData::answer_deadline`
+    /// is over.
+    fn resolve_timeout(question: C::Question) -> Self::Command;
+
+    async fn process_query(
+        supervisor: &Supervisor,
+    ) -> Result<Vec<ConsumerHandle>, RepositoryError> {
+        let mut handles = Vec::new();
+
+        for event_name in C::answer_names().into_iter().chain(C::refusal_names()) {
+            handles.push(Self::process(supervisor, event_name).await?);
+        }
+
+        Ok(handles)
+    }
+
+    /// Give the questions asked by the state a timeout command, applied by `scheduler`
+    /// to the asking stream unless an answer or a refusal to the question arrives before
+    /// `CrossData::answer_deadline`. Nothing is run without deadline.
+    ///
+    /// Each question has its own deadline, an answer cancelling the deadline of the
+    /// question it was caused by. The questions and their answers being received by
+    /// type, an answer may be received before its question: the schedule store keeps the
+    /// cancel until the timeout is scheduled. An answer arriving after the timeout goes
+    /// to the dead-letter stream if refused.
+    async fn process_deadline(
+        supervisor: &Supervisor,
+        scheduler: Scheduler,
+    ) -> Result<Vec<ConsumerHandle>, RepositoryError>
+    where
+        Self: 'static,
+    {
+        let deadline = match C::answer_deadline() {
+            Some(deadline) => deadline,
+            None => return Ok(Vec::new()),
+        };
+
+        scheduler.register::<Self>();
+
+        let consumer = format!("{}.deadline", Self::name_prefix());
+        let mut handles = Vec::new();
+
+        for event_name in C::question_names() {
+            let event_type = format!("{}.{}", EVENT_PREFIX, event_name);
+            let scheduler = scheduler.clone();
+
+            let handle = supervisor
+                .consume(consumer.clone(), event_type, move |repo, e| {
+                    let scheduler = scheduler.clone();
+
+                    async move {
+                        let metadata = e.metadata()?;
+                        let question = repo.decode_event::<C::Question>(&e)?;
+
+                        scheduler
+                            .schedule::<Self>(
+                                e.id(),
+                                &e.key(),
+                                Self::resolve_timeout(question),
+                                deadline,
+                                Some(&metadata),
+                            )
+                            .await
+                    }
+                })
+                .await?;
+
+            handles.push(handle);
+        }
+
+        for event_name in C::answer_names().into_iter().chain(C::refusal_names()) {
+            let event_type = format!("{}.{}", EVENT_PREFIX, event_name);
+            let scheduler = scheduler.clone();
+
+            let handle = supervisor
+                .consume(consumer.clone(), event_type, move |repo, e| {
+                    let scheduler = scheduler.clone();
+
+                    async move {
+                        let question_id = Self::question_id(&repo, &e).await?;
+
+                        scheduler.cancel(question_id).await.map(|_| ())
+                    }
+                })
+                .await?;
+
+            handles.push(handle);
         }
+
+        Ok(handles)
     }
 
-    fn resolve_helper(e: RecordedEvent) -> (Self::Command, ModelKey) {
-        let event = e.as_json::<C::Answer>().unwrap();
+    /// Id of the question `answer` was caused by, following its causation back through
+    /// its stream: the events of a command are caused one by the other and the command
+    /// by the question.
+    async fn question_id(
+        repo: &StateRepository,
+        answer: &StoredEvent,
+    ) -> Result<Uuid, RepositoryError> {
+        let events = repo.event_db().read_events(&answer.key(), None).await?;
+        let mut cause = answer.metadata()?.causation_id();
+
+        for stored in events.iter().rev() {
+            if stored.id() != cause {
+                continue;
+            }
+
+            let metadata = stored.metadata()?;
+            cause = metadata.causation_id();
+
+            if !metadata.is_event() {
+                break;
+            }
+        }
+
+        Ok(cause)
+    }
+
+    fn resolve_helper(
+        repo: &StateRepository,
+        e: StoredEvent,
+    ) -> Result<(Self::Command, ModelKey), RepositoryError> {
+        let refused = C::refusal_names()
+            .iter()
+            .any(|name| e.event_type() == format!("{}.{}", EVENT_PREFIX, name));
+
+        if refused {
+            let event = repo.decode_event::<C::Refusal>(&e)?;
+            let target = event.get_target();
+            return Ok((Self::resolve_refusal(event), target));
+        }
+
+        let event = repo.decode_event::<C::Answer>(&e)?;
         let target = event.get_target();
         let cmd = Self::resolve_answer(event);
-        (cmd, target)
+        Ok((cmd, target))
     }
 }
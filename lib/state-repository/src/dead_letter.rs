@@ -0,0 +1,207 @@
+use crate::error::RepositoryError;
+use crate::metadata::{EventWithMetadata, Metadata};
+use crate::model_key::ModelKey;
+use crate::schedule::ScheduledCommand;
+use crate::storage::{json_or_text, StoredEvent};
+use crate::StateRepository;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+const DEAD_LETTER_PREFIX: &str = "dead_letter";
+const RESOLVED_PREFIX: &str = "dead_letter_resolved";
+
+/// Event type of the dead letters of a consumer, like `dead_letter.test-gold.cross`.
+pub fn dead_letter_event_type(consumer: &str) -> String {
+    format!("{DEAD_LETTER_PREFIX}.{consumer}")
+}
+
+fn resolved_event_type(consumer: &str) -> String {
+    format!("{RESOLVED_PREFIX}.{consumer}")
+}
+
+/// Event a processor failed to handle, with the error, kept aside so that the
+/// following events are still handled.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DeadLetter {
+    consumer: String,
+    event_type: String,
+    stream_id: String,
+    event_id: Uuid,
+    revision: u64,
+    position: Option<u64>,
+    data: Value,
+    metadata: Value,
+    error: String,
+}
+
+/// How a dead letter left its stream.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    /// Handled again successfully.
+    Retried,
+    /// Given up on.
+    Discarded,
+}
+
+impl DeadLetter {
+    pub(crate) fn new(consumer: &str, event: &StoredEvent, error: &RepositoryError) -> Self {
+        Self {
+            consumer: consumer.to_string(),
+            event_type: event.event_type().to_string(),
+            stream_id: event.stream_id().to_string(),
+            event_id: event.id(),
+            revision: event.revision(),
+            position: event.position(),
+            data: json_or_text(event.data()),
+            metadata: json_or_text(event.custom_metadata()),
+            error: error.to_string(),
+        }
+    }
+
+    /// Scheduled command which cannot be applied, under the event type
+    /// `scheduled.{state name}` with the id of the command.
+    pub(crate) fn scheduled(
+        consumer: &str,
+        scheduled: &ScheduledCommand,
+        error: &RepositoryError,
+    ) -> Self {
+        Self {
+            consumer: consumer.to_string(),
+            event_type: format!("scheduled.{}", scheduled.state_name()),
+            stream_id: scheduled.key().format(),
+            event_id: scheduled.id(),
+            revision: 0,
+            position: None,
+            data: scheduled.command().clone(),
+            metadata: serde_json::to_value(scheduled.metadata()).unwrap_or(Value::Null),
+            error: error.to_string(),
+        }
+    }
+
+    /// Name of the processor which failed.
+    pub fn consumer(&self) -> &str {
+        &self.consumer
+    }
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+    pub fn stream_id(&self) -> &str {
+        &self.stream_id
+    }
+    pub fn event_id(&self) -> Uuid {
+        self.event_id
+    }
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+    pub fn position(&self) -> Option<u64> {
+        self.position
+    }
+    pub fn data(&self) -> &Value {
+        &self.data
+    }
+    pub fn metadata(&self) -> &Value {
+        &self.metadata
+    }
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// Stream of a dead letter, one per consumer and failed event, holding the dead letter
+/// then its resolution.
+fn key(consumer: &str, event_id: Uuid) -> ModelKey {
+    ModelKey::new(
+        DEAD_LETTER_PREFIX.to_string(),
+        format!("{}-{}", consumer.replace('.', "_"), event_id),
+    )
+}
+
+/// Keep a failed event in its dead-letter stream, an event already kept for the
+/// consumer being ignored.
+pub(crate) async fn send(
+    repo: &StateRepository,
+    dead_letter: DeadLetter,
+    failed: Option<&Metadata>,
+) -> Result<(), RepositoryError> {
+    let key = key(&dead_letter.consumer, dead_letter.event_id);
+    let entry = EventWithMetadata::from_dead_letter(
+        dead_letter_event_type(&dead_letter.consumer),
+        serde_json::to_value(dead_letter)?,
+        failed,
+    )
+    .with_timestamp(repo.context.now());
+
+    match repo.event_db.append_events(&key, None, vec![entry]).await {
+        Err(RepositoryError::Conflict(_)) => Ok(()),
+        appended => appended,
+    }
+}
+
+/// Dead letters of a consumer not resolved yet, the oldest first.
+pub(crate) async fn list(
+    repo: &StateRepository,
+    consumer: &str,
+) -> Result<Vec<DeadLetter>, RepositoryError> {
+    let resolved = repo
+        .event_db
+        .read_event_type(&resolved_event_type(consumer))
+        .await?
+        .iter()
+        .map(|entry| entry.stream_id().to_string())
+        .collect::<HashSet<String>>();
+
+    repo.event_db
+        .read_event_type(&dead_letter_event_type(consumer))
+        .await?
+        .iter()
+        .filter(|entry| !resolved.contains(entry.stream_id()))
+        .map(|entry| Ok(entry.as_json()?))
+        .collect()
+}
+
+/// Dead letter of a consumer for an event, if not resolved yet.
+pub(crate) async fn inspect(
+    repo: &StateRepository,
+    consumer: &str,
+    event_id: Uuid,
+) -> Result<Option<DeadLetter>, RepositoryError> {
+    let entries = repo
+        .event_db
+        .read_events(&key(consumer, event_id), None)
+        .await?;
+
+    match entries.as_slice() {
+        [dead_letter] => Ok(Some(dead_letter.as_json()?)),
+        _ => Ok(None),
+    }
+}
+
+/// Close a dead letter, telling whether it was still open.
+pub(crate) async fn resolve(
+    repo: &StateRepository,
+    dead_letter: &DeadLetter,
+    resolution: Resolution,
+) -> Result<bool, RepositoryError> {
+    let entry = EventWithMetadata::from_dead_letter(
+        resolved_event_type(&dead_letter.consumer),
+        serde_json::to_value(resolution)?,
+        None,
+    )
+    .with_timestamp(repo.context.now());
+
+    let key = key(&dead_letter.consumer, dead_letter.event_id);
+
+    match repo
+        .event_db
+        .append_events(&key, Some(0), vec![entry])
+        .await
+    {
+        Ok(()) => Ok(true),
+        Err(RepositoryError::Conflict(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
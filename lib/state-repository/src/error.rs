@@ -0,0 +1,154 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    /// The stream has moved since it was read, the command can be tried again.
+    Conflict(String),
+    /// The retry policy gave up on conflicts, the last one being given.
+    RetryExhausted { attempts: u32, conflict: String },
+    /// An event, its metadata or a cached state cannot be (de)serialized.
+    Deserialization(anyhow::Error),
+    /// The event storage cannot be reached or failed.
+    StoreUnavailable(anyhow::Error),
+    /// The snapshot store cannot be reached or failed, or a snapshot cannot be decoded.
+    Snapshot(anyhow::Error),
+    /// The checkpoint store cannot be reached or failed.
+    Checkpoint(anyhow::Error),
+    /// A projection failed to apply an event or to reset its read model.
+    Projection(anyhow::Error),
+    /// The store of scheduled commands cannot be reached or failed.
+    Schedule(anyhow::Error),
+    /// A processor of events failed to handle one of them, or panicked.
+    Processing(anyhow::Error),
+    /// The awaited outcome of an applied command did not come in time.
+    Timeout(Duration),
+}
+
+/// Error of a command given to `StateRepository::add_command`, `E` being the domain
+/// error of its state.
+#[derive(Debug)]
+pub enum CommandError<E> {
+    /// `State::try_command` refused the command.
+    Rejected(E),
+    /// The command could not be applied.
+    Repository(RepositoryError),
+}
+
+impl<E> CommandError<E> {
+    /// Domain error given by the state when it refused the command.
+    pub fn rejection(&self) -> Option<&E> {
+        match self {
+            CommandError::Rejected(e) => Some(e),
+            CommandError::Repository(_) => None,
+        }
+    }
+
+    /// Same error, the domain error being turned by `f`, as for a state not known where
+    /// the command is applied.
+    pub(crate) fn map_rejection<F>(self, f: impl FnOnce(E) -> F) -> CommandError<F> {
+        match self {
+            CommandError::Rejected(e) => CommandError::Rejected(f(e)),
+            CommandError::Repository(e) => CommandError::Repository(e),
+        }
+    }
+}
+
+impl<E: Display> CommandError<E> {
+    /// Failure of a processor applying the command for an event, which goes to the dead
+    /// letters when the command is refused.
+    pub(crate) fn into_processing(self) -> RepositoryError {
+        match self {
+            CommandError::Rejected(e) => {
+                RepositoryError::Processing(anyhow::anyhow!("command rejected : {e}"))
+            }
+            CommandError::Repository(e) => e,
+        }
+    }
+}
+
+impl<E: Display> Display for CommandError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Rejected(e) => write!(f, "command rejected : {e}"),
+            CommandError::Repository(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Display + std::fmt::Debug> Error for CommandError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CommandError::Rejected(_) => None,
+            CommandError::Repository(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<RepositoryError> for CommandError<E> {
+    fn from(e: RepositoryError) -> Self {
+        CommandError::Repository(e)
+    }
+}
+
+impl RepositoryError {
+    /// Whether the same operation may succeed later, a store coming back or a conflict
+    /// going away, whereas a refused command or a malformed event always fails.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RepositoryError::Conflict(_)
+            | RepositoryError::RetryExhausted { .. }
+            | RepositoryError::StoreUnavailable(_)
+            | RepositoryError::Snapshot(_)
+            | RepositoryError::Checkpoint(_)
+            | RepositoryError::Schedule(_) => true,
+            RepositoryError::Deserialization(_)
+            | RepositoryError::Projection(_)
+            | RepositoryError::Processing(_)
+            | RepositoryError::Timeout(_) => false,
+        }
+    }
+}
+
+impl Display for RepositoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepositoryError::Conflict(e) => write!(f, "concurrency conflict : {e}"),
+            RepositoryError::RetryExhausted { attempts, conflict } => {
+                write!(f, "retry exhausted after {attempts} attempts : {conflict}")
+            }
+            RepositoryError::Deserialization(e) => write!(f, "deserialization : {e:#}"),
+            RepositoryError::StoreUnavailable(e) => write!(f, "store unavailable : {e:#}"),
+            RepositoryError::Snapshot(e) => write!(f, "snapshot : {e:#}"),
+            RepositoryError::Checkpoint(e) => write!(f, "checkpoint : {e:#}"),
+            RepositoryError::Projection(e) => write!(f, "projection : {e:#}"),
+            RepositoryError::Schedule(e) => write!(f, "schedule : {e:#}"),
+            RepositoryError::Processing(e) => write!(f, "processing : {e:#}"),
+            RepositoryError::Timeout(timeout) => write!(f, "no outcome after {timeout:?}"),
+        }
+    }
+}
+
+impl Error for RepositoryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RepositoryError::Conflict(_)
+            | RepositoryError::RetryExhausted { .. }
+            | RepositoryError::Timeout(_) => None,
+            RepositoryError::Deserialization(e)
+            | RepositoryError::StoreUnavailable(e)
+            | RepositoryError::Snapshot(e)
+            | RepositoryError::Checkpoint(e)
+            | RepositoryError::Projection(e)
+            | RepositoryError::Schedule(e)
+            | RepositoryError::Processing(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for RepositoryError {
+    fn from(e: serde_json::Error) -> Self {
+        RepositoryError::Deserialization(e.into())
+    }
+}
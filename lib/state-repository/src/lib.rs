@@ -1,29 +1,61 @@
+pub mod causation;
+pub mod checkpoint;
+pub mod clock;
+pub mod consumer;
 pub mod cross_state;
+pub mod dead_letter;
+pub mod error;
 pub mod metadata;
 pub mod model_key;
+pub mod options;
+pub mod projection;
+mod redis_connection;
+pub mod retry;
+pub mod saga;
+pub mod schedule;
+pub mod snapshot;
+pub mod storage;
+pub mod upcast;
 pub mod waiter;
 
-use anyhow::{anyhow, Context, Result};
-use eventstore::{
-    AppendToStreamOptions, Client as EventDb, Error, EventData, ExpectedRevision,
-    ReadStreamOptions, StreamPosition,
-};
+use anyhow::{anyhow, Context};
+use causation::CausationNode;
+use error::{CommandError, RepositoryError};
+use futures::stream::{self, StreamExt};
 use metadata::{EventWithMetadata, Metadata};
 use model_key::ModelKey;
-use redis::Client as CacheDb;
-use redis::Commands;
+use options::CommandOptions;
+use retry::RetryPolicy;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use state::State;
+use snapshot::{Snapshot, SnapshotStore};
+use state::clock::Clock;
+use state::context::IdGenerator;
+use state::{CommandContext, State};
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use storage::{EventStorage, StoredEvent, SubscriptionStart};
+use tokio::time::{sleep, timeout, Duration, Instant};
+use upcast::Upcasters;
+use uuid::Uuid;
 
 const COMMAND_PREFIX: &str = "cmd";
 const EVENT_PREFIX: &str = "evt";
 
+type SnapshotFailure = dyn Fn(&RepositoryError) + Send + Sync;
+
 #[derive(Clone)]
 pub struct StateRepository {
-    event_db: EventDb,
-    cache_db: CacheDb,
+    event_db: Arc<dyn EventStorage>,
+    snapshot_db: Arc<dyn SnapshotStore>,
+    retry_policy: RetryPolicy,
+    upcasters: Upcasters,
+    service: Option<Arc<str>>,
+    context: CommandContext,
+    conflicts: Arc<AtomicU64>,
+    snapshot_failures: Arc<AtomicU64>,
+    on_snapshot_failure: Option<Arc<SnapshotFailure>>,
 }
 
 #[derive(Default, Serialize, Deserialize, Debug, Clone)]
@@ -44,78 +76,188 @@ impl<S> StateWithInfo<S> {
 }
 
 impl StateRepository {
-    pub fn new(event_db: EventDb, cache_db: CacheDb) -> Self {
-        Self { event_db, cache_db }
+    pub fn new<E, P>(event_db: E, snapshot_db: P) -> Self
+    where
+        E: EventStorage + 'static,
+        P: SnapshotStore + 'static,
+    {
+        Self {
+            event_db: Arc::new(event_db),
+            snapshot_db: Arc::new(snapshot_db),
+            retry_policy: RetryPolicy::default(),
+            upcasters: Upcasters::default(),
+            service: None,
+            context: CommandContext::default(),
+            conflicts: Arc::new(AtomicU64::new(0)),
+            snapshot_failures: Arc::new(AtomicU64::new(0)),
+            on_snapshot_failure: None,
+        }
+    }
+
+    /// Retry policy of the commands added without their own one.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Upcasters of the stored events older than their current `Event` type.
+    pub fn with_upcasters(mut self, upcasters: Upcasters) -> Self {
+        self.upcasters = upcasters;
+        self
+    }
+
+    /// Name of the service applying the commands, kept in their metadata.
+    pub fn with_service(mut self, service: impl Into<String>) -> Self {
+        self.service = Some(service.into().into());
+        self
+    }
+
+    /// Clock read by the commands, the system one by default.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.context = self.context.with_clock(clock);
+        self
+    }
+
+    /// Generator of the ids created by the commands, random ones by default.
+    pub fn with_id_generator(mut self, ids: impl IdGenerator + 'static) -> Self {
+        self.context = self.context.with_ids(ids);
+        self
+    }
+
+    /// Called with the error of a snapshot that failed to load or save, the model being
+    /// replayed without it.
+    pub fn with_snapshot_failure_hook(
+        mut self,
+        hook: impl Fn(&RepositoryError) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_snapshot_failure = Some(Arc::new(hook));
+        self
     }
 
-    pub async fn get_model<S>(&self, key: &ModelKey) -> Result<StateWithInfo<S>>
+    /// Concurrency conflicts met by the commands of this repository and its clones.
+    pub fn conflict_count(&self) -> u64 {
+        self.conflicts.load(Ordering::Relaxed)
+    }
+
+    pub async fn get_model<S>(&self, key: &ModelKey) -> Result<StateWithInfo<S>, RepositoryError>
     where
         S: State + DeserializeOwned,
     {
-        let value = if S::state_cache_interval().is_some() {
-            let mut cache_connection = self
-                .cache_db
-                .get_connection()
-                .context("connect to cache db")?;
-            let data: String = cache_connection
-                .get(key.format())
-                .context("get from cache")
-                .unwrap_or_default();
-            serde_json::from_str(data.as_str()).unwrap_or_default()
-        } else {
-            StateWithInfo::default()
-        };
-
-        let mut state: S = value.state;
-        let mut info = value.info;
+        let mut state = S::default();
+        let mut info = StateInformation::default();
+
+        if S::snapshot_interval().is_some() {
+            // a snapshot is only a cache, the stream is replayed from the start without it
+            match self.load_snapshot::<S>(key).await {
+                Ok(Some((snapshot_state, revision))) => {
+                    state = snapshot_state;
+                    info.position = Some(revision);
+                }
+                Ok(None) => {}
+                Err(error) => self.snapshot_failed(&error),
+            }
+        }
 
-        let options = ReadStreamOptions::default();
-        let options = if let Some(position) = info.position {
-            options.position(StreamPosition::Position(position + 1))
-        } else {
-            options.position(StreamPosition::Start)
-        };
+        let snapshot_position = info.position;
 
-        let mut stream = self
+        let events = self
             .event_db
-            .read_stream(key.format(), &options)
-            .await
-            .context("connect to event db")?;
-
-        let mut nb_change = 0;
-
-        while let Ok(Some(json_event)) = stream.next().await {
-            let original_event = json_event.get_original_event();
+            .read_events(key, info.position.map(|position| position + 1))
+            .await?;
 
-            let metadata: Metadata =
-                serde_json::from_slice(original_event.custom_metadata.as_ref()).unwrap();
+        for stored_event in events {
+            let metadata = stored_event.metadata()?;
 
             if metadata.is_event() {
-                let event = original_event
-                    .as_json::<S::Event>()
-                    .context(format!("decode event : {:?}", json_event))?;
+                let event = self.decode_event::<S::Event>(&stored_event)?;
 
                 state.play_event(&event);
-                nb_change += 1;
             }
 
-            info.position = Some(original_event.revision)
+            info.position = Some(stored_event.revision())
+        }
+
+        if let (Some(interval), Some(position)) = (S::snapshot_interval(), info.position) {
+            let revisions = match snapshot_position {
+                None => position + 1,
+                Some(snapshot_position) => position - snapshot_position,
+            };
+
+            if revisions >= interval {
+                let saved = match Snapshot::new(S::snapshot_version(), position, &state) {
+                    Ok(snapshot) => self.snapshot_db.save(key, snapshot).await,
+                    Err(error) => Err(error.into()),
+                };
+
+                if let Err(error) = saved {
+                    let error = error.context(format!("save snapshot of {}", key.format()));
+                    self.snapshot_failed(&RepositoryError::Snapshot(error));
+                }
+            }
         }
 
         let result = StateWithInfo { info, state };
 
-        if S::state_cache_interval().is_some() && nb_change > S::state_cache_interval().unwrap() {
-            let mut cache_connection = self
-                .cache_db
-                .get_connection()
-                .context("connect to cache db")?;
+        Ok(result)
+    }
+
+    /// Snapshots that failed to load or save, the models being replayed without them.
+    pub fn snapshot_failure_count(&self) -> u64 {
+        self.snapshot_failures.load(Ordering::Relaxed)
+    }
+
+    async fn load_snapshot<S>(&self, key: &ModelKey) -> Result<Option<(S, u64)>, RepositoryError>
+    where
+        S: State + DeserializeOwned,
+    {
+        let snapshot = self
+            .snapshot_db
+            .load(key)
+            .await
+            .context(format!("load snapshot of {}", key.format()))
+            .map_err(RepositoryError::Snapshot)?;
+
+        // a snapshot of an other version of the state is replayed from the start
+        match snapshot.filter(|s| s.version() == S::snapshot_version()) {
+            None => Ok(None),
+            Some(snapshot) => {
+                let state = snapshot
+                    .state()
+                    .context(format!("decode snapshot of {}", key.format()))
+                    .map_err(RepositoryError::Snapshot)?;
+
+                Ok(Some((state, snapshot.revision())))
+            }
+        }
+    }
 
-            cache_connection
-                .set(key.format(), serde_json::to_string(&result)?)
-                .context("set cache value")?;
+    fn snapshot_failed(&self, error: &RepositoryError) {
+        self.snapshot_failures.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(hook) = &self.on_snapshot_failure {
+            hook(error);
         }
+    }
 
-        Ok(result)
+    /// Decode a stored event, upcasted first if it was stored with an older version.
+    pub fn decode_event<E>(&self, stored_event: &StoredEvent) -> Result<E, RepositoryError>
+    where
+        E: DeserializeOwned,
+    {
+        let metadata = stored_event.metadata()?;
+
+        let data = self
+            .upcasters
+            .upcast(
+                stored_event.event_type(),
+                metadata.event_version(),
+                stored_event.as_json()?,
+            )
+            .map_err(RepositoryError::Deserialization)?;
+
+        serde_json::from_value(data)
+            .context(format!("decode event : {:?}", stored_event))
+            .map_err(RepositoryError::Deserialization)
     }
 
     pub async fn add_command<T>(
@@ -123,26 +265,60 @@ impl StateRepository {
         key: &ModelKey,
         command: T::Command,
         previous_metadata: Option<&Metadata>,
-    ) -> Result<T>
+    ) -> Result<T, CommandError<T::Error>>
     where
         T: State,
     {
-        let mut model: T;
-        let events: Vec<T::Event>;
-
-        loop {
-            let (l_model, l_events, retry) = self
-                .try_append(key, command.clone(), previous_metadata)
-                .await?;
-            if retry {
-                continue;
-            }
+        self.add_command_with_options(key, command, previous_metadata, &CommandOptions::default())
+            .await
+    }
+
+    pub async fn add_command_with_options<T>(
+        &self,
+        key: &ModelKey,
+        command: T::Command,
+        previous_metadata: Option<&Metadata>,
+        options: &CommandOptions,
+    ) -> Result<T, CommandError<T::Error>>
+    where
+        T: State,
+    {
+        let retry_policy = options.get_retry_policy().unwrap_or(&self.retry_policy);
+        let started = Instant::now();
+        let mut attempt = 0;
 
-            model = l_model;
-            events = l_events;
+        let idempotency_key = options.get_idempotency_key();
 
-            break;
-        }
+        let (mut model, events) = loop {
+            attempt += 1;
+
+            if let Some(idempotency_key) = idempotency_key {
+                if let Some(model) = self.replay_command::<T>(key, idempotency_key).await? {
+                    return Ok(model);
+                }
+            }
+
+            match self
+                .try_append::<T>(key, command.clone(), previous_metadata, options)
+                .await
+            {
+                Err(CommandError::Repository(RepositoryError::Conflict(conflict))) => {
+                    self.conflicts.fetch_add(1, Ordering::Relaxed);
+
+                    match retry_policy.next_backoff(attempt, started) {
+                        Some(backoff) => sleep(backoff).await,
+                        None => {
+                            return Err(RepositoryError::RetryExhausted {
+                                attempts: attempt,
+                                conflict,
+                            }
+                            .into())
+                        }
+                    }
+                }
+                appended => break appended?,
+            }
+        };
 
         for event in &events {
             model.play_event(event);
@@ -151,30 +327,138 @@ impl StateRepository {
         Ok(model)
     }
 
+    /// Apply a command then wait for the first event of `event_types` carrying the
+    /// correlation id of the command and accepted by `predicate`, so that the outcome of
+    /// a flow across states is known without polling.
+    ///
+    /// Only the events appended after the call are awaited. Failing with
+    /// `RepositoryError::Timeout` does not undo the command, whose outcome may still come.
+    pub async fn add_command_and_wait<S, P>(
+        &self,
+        key: &ModelKey,
+        command: S::Command,
+        previous_metadata: Option<&Metadata>,
+        event_types: &[&str],
+        predicate: P,
+        wait: Duration,
+    ) -> Result<StoredEvent, CommandError<S::Error>>
+    where
+        S: State,
+        P: Fn(&StoredEvent) -> bool,
+    {
+        // a command without previous metadata starts its own correlation, known only once
+        // stored, so one is started here
+        let metadata = match previous_metadata {
+            Some(metadata) => metadata.clone(),
+            None => {
+                let id = Uuid::new_v4();
+                Metadata::new(None, id, id, false)
+            }
+        };
+        let correlation_id = metadata.correlation_id();
+
+        // subscribed before the command so that an outcome coming at once is not missed
+        let mut subscriptions = Vec::new();
+        for event_type in event_types {
+            subscriptions.push(
+                self.event_db
+                    .subscribe_event_type(event_type, SubscriptionStart::End)
+                    .await?,
+            );
+        }
+        let mut events = stream::select_all(subscriptions);
+
+        self.add_command::<S>(key, command, Some(&metadata)).await?;
+
+        let outcome = async {
+            while let Some(event) = events.next().await {
+                let event = event?;
+
+                if event.metadata()?.correlation_id() == correlation_id && predicate(&event) {
+                    return Ok(event);
+                }
+            }
+
+            Err(RepositoryError::StoreUnavailable(anyhow!(
+                "subscriptions ended before the outcome"
+            )))
+        };
+
+        match timeout(wait, outcome).await {
+            Ok(outcome) => Ok(outcome?),
+            Err(_) => Err(RepositoryError::Timeout(wait).into()),
+        }
+    }
+
+    /// State right after the command with this idempotency key, if it is on the stream.
+    ///
+    /// The key is looked up in the index of the storage, the stream being replayed only
+    /// when it is found, up to the events of its command.
+    async fn replay_command<S>(
+        &self,
+        key: &ModelKey,
+        idempotency_key: &str,
+    ) -> Result<Option<S>, RepositoryError>
+    where
+        S: State,
+    {
+        let command = match self
+            .event_db
+            .read_idempotency_key(key, idempotency_key)
+            .await?
+        {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+
+        let mut state = S::default();
+
+        for stored_event in self.event_db.read_events(key, None).await? {
+            let metadata = stored_event.metadata()?;
+
+            if !metadata.is_event() {
+                if stored_event.revision() > command.revision() {
+                    break;
+                }
+                continue;
+            }
+
+            let event = self.decode_event::<S::Event>(&stored_event)?;
+
+            state.play_event(&event);
+        }
+
+        Ok(Some(state))
+    }
+
     async fn try_append<S>(
         &self,
         key: &ModelKey,
         command: S::Command,
         previous_metadata: Option<&Metadata>,
-    ) -> Result<(S, Vec<S::Event>, bool)>
+        options: &CommandOptions,
+    ) -> Result<(S, Vec<S::Event>), CommandError<S::Error>>
     where
         S: State,
     {
-        let model: StateWithInfo<S> = self.get_model(key).await.context("adding command")?;
+        let model: StateWithInfo<S> = self.get_model(key).await?;
 
         let state = model.state;
         let info = model.info;
 
-        let events = state.try_command(command.clone()).context("try command")?;
+        let command_metadata =
+            EventWithMetadata::from_command(command.clone(), previous_metadata, S::name_prefix())
+                .with_origin(options, self.service.as_deref());
 
-        let options = if let Some(position) = info.position {
-            AppendToStreamOptions::default().expected_revision(ExpectedRevision::Exact(position))
-        } else {
-            AppendToStreamOptions::default().expected_revision(ExpectedRevision::NoStream)
-        };
+        let actor = command_metadata.metadata().actor().map(str::to_string);
+        let context = self.context.clone().with_actor(actor);
 
-        let command_metadata =
-            EventWithMetadata::from_command(command, previous_metadata, S::name_prefix());
+        let now = context.now();
+        let command_metadata = command_metadata.with_timestamp(now);
+
+        let events = state
+            .try_command(command, &context)
+            .map_err(CommandError::Rejected)?;
 
         let mut events_data = vec![command_metadata.clone()];
 
@@ -184,57 +468,45 @@ impl StateRepository {
 
         for event in events {
             let event_metadata =
-                EventWithMetadata::from_event(event, &previous_metadata, S::name_prefix());
+                EventWithMetadata::from_event(event, &previous_metadata, S::name_prefix())
+                    .with_timestamp(now);
 
             events_data.push(event_metadata.clone());
             previous_metadata = event_metadata.metadata().to_owned();
         }
 
-        let retry = self
-            .try_append_event_data(key, &options, events_data)
+        self.try_append_event_data(key, info.position, events_data)
             .await?;
 
-        Ok((state, res_events, retry))
+        Ok((state, res_events))
+    }
+
+    /// Commands and events of every stream carrying `correlation_id`, as the trees of
+    /// what caused what, to follow a flow across states.
+    pub async fn causation_tree(
+        &self,
+        correlation_id: Uuid,
+    ) -> Result<Vec<CausationNode>, RepositoryError> {
+        let events = self.event_db.read_correlation(correlation_id).await?;
+
+        causation::causation_tree(events)
     }
 
     pub async fn try_append_event_data(
         &self,
         key: &ModelKey,
-        options: &AppendToStreamOptions,
+        expected_revision: Option<u64>,
         events_with_data: Vec<EventWithMetadata>,
-    ) -> Result<bool> {
-        let events: Vec<EventData> = events_with_data
-            .into_iter()
-            .map(|e| e.full_event_data())
-            .collect();
-
-        let appended = self
-            .event_db
-            .append_to_stream(key.format(), options, events)
-            .await;
-
-        let mut retry = false;
-
-        if appended.is_err() {
-            let err = appended.unwrap_err();
-            match err {
-                Error::WrongExpectedVersion { expected, current } => {
-                    println!("{current} instead of {expected}");
-
-                    retry = true;
-                }
-                _ => {
-                    return Err(anyhow!("error while appending : {:?}", err));
-                }
-            }
-        }
-        Ok(retry)
+    ) -> Result<(), RepositoryError> {
+        self.event_db
+            .append_events(key, expected_revision, events_with_data)
+            .await
     }
 
-    pub fn event_db(&self) -> &EventDb {
-        &self.event_db
+    pub fn event_db(&self) -> &dyn EventStorage {
+        self.event_db.as_ref()
     }
-    pub fn cache_db(&self) -> &CacheDb {
-        &self.cache_db
+    pub fn snapshot_db(&self) -> &dyn SnapshotStore {
+        self.snapshot_db.as_ref()
     }
 }
@@ -1,7 +1,10 @@
+use crate::options::CommandOptions;
 use crate::{COMMAND_PREFIX, EVENT_PREFIX};
-use eventstore::EventData;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use state::{Command, Event, StateName};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -14,6 +17,34 @@ pub struct Metadata {
     causation_id: Uuid,
     #[serde(rename = "is_event")]
     is_event: bool,
+    #[serde(
+        rename = "$idempotencyKey",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    idempotency_key: Option<String>,
+    #[serde(
+        rename = "$eventVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    event_version: Option<u32>,
+    #[serde(rename = "$actor", default, skip_serializing_if = "Option::is_none")]
+    actor: Option<String>,
+    #[serde(
+        rename = "$timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    timestamp: Option<u64>,
+    #[serde(rename = "$service", default, skip_serializing_if = "Option::is_none")]
+    service: Option<String>,
+    #[serde(
+        rename = "$headers",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    headers: BTreeMap<String, String>,
 }
 
 impl Metadata {
@@ -36,32 +67,100 @@ impl Metadata {
             correlation_id,
             causation_id,
             is_event,
+            idempotency_key: None,
+            event_version: None,
+            actor: None,
+            timestamp: None,
+            service: None,
+            headers: BTreeMap::new(),
         }
     }
     pub fn is_event(&self) -> bool {
         self.is_event
     }
+    /// Key given by the client to the command, so that the command is only applied once.
+    pub fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+    /// `Event::event_version` of the event when it was stored, events stored before
+    /// versioning being the first version.
+    pub fn event_version(&self) -> u32 {
+        self.event_version.unwrap_or(1)
+    }
+    /// User on whose behalf the command was applied, kept by everything it caused.
+    pub fn actor(&self) -> Option<&str> {
+        self.actor.as_deref()
+    }
+    /// Time of the repository clock when the command or event was created, unknown for
+    /// the ones created before it was recorded.
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.timestamp
+            .map(|timestamp| UNIX_EPOCH + Duration::from_millis(timestamp))
+    }
+    /// Service which applied the command.
+    pub fn service(&self) -> Option<&str> {
+        self.service.as_deref()
+    }
+    /// Headers given to the command, kept by everything it caused.
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        &self.headers
+    }
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct EventWithMetadata {
-    event_data: EventData,
+    id: Uuid,
+    event_type: String,
+    data: Value,
     metadata: Metadata,
 }
 
 impl EventWithMetadata {
-    pub fn event_data(&self) -> &EventData {
-        &self.event_data
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+    pub fn data(&self) -> &Value {
+        &self.data
     }
     pub fn metadata(&self) -> &Metadata {
         &self.metadata
     }
 
-    pub fn full_event_data(&self) -> EventData {
-        self.event_data
-            .clone()
-            .metadata_as_json(self.metadata())
-            .unwrap()
+    /// Time the entry was created, read from the clock of the repository storing it.
+    pub(crate) fn with_timestamp(mut self, now: SystemTime) -> Self {
+        self.metadata.timestamp = now
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|now| now.as_millis() as u64);
+        self
+    }
+
+    pub fn with_idempotency_key(mut self, idempotency_key: Option<String>) -> Self {
+        self.metadata.idempotency_key = idempotency_key;
+        self
+    }
+
+    /// Origin of a command given by its options and the service applying it, replacing
+    /// the one of its cause.
+    pub(crate) fn with_origin(mut self, options: &CommandOptions, service: Option<&str>) -> Self {
+        let metadata = &mut self.metadata;
+
+        metadata.idempotency_key = options.get_idempotency_key().map(str::to_string);
+        if let Some(actor) = options.get_actor() {
+            metadata.actor = Some(actor.to_string());
+        }
+        if let Some(service) = service {
+            metadata.service = Some(service.to_string());
+        }
+        metadata.headers.extend(options.get_headers().clone());
+
+        self
     }
 
     pub fn from_command<C>(
@@ -72,54 +171,60 @@ impl EventWithMetadata {
     where
         C: Command,
     {
-        let event_data = EventData::json(
-            format!(
-                "{}.{}.{}",
-                COMMAND_PREFIX,
-                state_name,
-                command.command_name()
-            ),
-            command,
-        )
-        .unwrap();
+        let event_type = format!(
+            "{}.{}.{}",
+            COMMAND_PREFIX,
+            state_name,
+            command.command_name()
+        );
+        let data = serde_json::to_value(command).unwrap();
 
-        Self::from_event_data(event_data, previous_metadata, false)
+        Self::from_event_data(event_type, data, previous_metadata, false)
     }
 
     pub fn from_event<E>(event: E, previous_metadata: &Metadata, state_name: StateName) -> Self
     where
         E: Event,
     {
-        println!("{:?}", event);
-        println!("{:?}", event.is_state_specific());
         let key = if event.is_state_specific() {
             format!("{}.{}.{}", EVENT_PREFIX, state_name, event.event_name())
         } else {
             format!("{}.{}", EVENT_PREFIX, event.event_name())
         };
-        println!("{key:?}");
 
-        let event_data = EventData::json(key, event).unwrap();
+        let event_version = event.event_version();
+        let data = serde_json::to_value(event).unwrap();
+
+        let mut event_data = Self::from_event_data(key, data, Some(previous_metadata), true);
+        event_data.metadata.event_version = Some(event_version);
+        event_data
+    }
+
+    /// Entry of a snapshot stream, neither a command nor an event of the state.
+    pub(crate) fn from_snapshot(event_type: String, data: Value) -> Self {
+        Self::from_event_data(event_type, data, None, false)
+    }
 
-        Self::from_event_data(event_data, Some(previous_metadata), true)
+    /// Entry of a dead-letter stream, caused by the event that failed when its metadata
+    /// can be read.
+    pub(crate) fn from_dead_letter(
+        event_type: String,
+        data: Value,
+        failed: Option<&Metadata>,
+    ) -> Self {
+        Self::from_event_data(event_type, data, failed, false)
     }
 
     fn from_event_data(
-        mut event_data: EventData,
+        event_type: String,
+        data: Value,
         previous_metadata: Option<&Metadata>,
         is_event: bool,
     ) -> Self {
         let id = Uuid::new_v4();
 
-        event_data = event_data.id(id);
-
         let metadata = match previous_metadata {
-            None => Metadata {
-                id: Some(id),
-                correlation_id: id,
-                causation_id: id,
-                is_event,
-            },
+            None => Metadata::new(Some(id), id, id, is_event),
             Some(previous) => Metadata {
                 id: Some(id),
                 correlation_id: previous.correlation_id,
@@ -128,11 +233,18 @@ impl EventWithMetadata {
                     Some(p) => p,
                 },
                 is_event,
+                idempotency_key: None,
+                event_version: None,
+                actor: previous.actor.clone(),
+                timestamp: None,
+                service: previous.service.clone(),
+                headers: previous.headers.clone(),
             },
         };
-
         Self {
-            event_data,
+            id,
+            event_type,
+            data,
             metadata,
         }
     }
@@ -14,6 +14,14 @@ impl ModelKey {
         }
     }
 
+    pub fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
+
+    pub fn stream_id(&self) -> &str {
+        &self.stream_id
+    }
+
     pub fn format(&self) -> String {
         format!("{}.{}", self.stream_name.replace('.', "_"), self.stream_id)
     }
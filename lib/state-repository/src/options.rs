@@ -0,0 +1,56 @@
+use crate::retry::RetryPolicy;
+use std::collections::BTreeMap;
+
+/// Options of a single `StateRepository::add_command_with_options` call.
+#[derive(Clone, Debug, Default)]
+pub struct CommandOptions {
+    retry_policy: Option<RetryPolicy>,
+    idempotency_key: Option<String>,
+    actor: Option<String>,
+    headers: BTreeMap<String, String>,
+}
+
+impl CommandOptions {
+    /// Replace the retry policy of the repository for this command.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Key of the command given by the client. A command whose key is already on the
+    /// stream is not applied again, the state right after its first application is
+    /// returned instead.
+    pub fn idempotency_key(mut self, idempotency_key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(idempotency_key.into());
+        self
+    }
+
+    /// User on whose behalf the command is applied, like the uuid of its token. The
+    /// commands caused by it keep it, unless given their own.
+    pub fn actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    /// Header kept in the metadata of the command and of everything it causes.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub(crate) fn get_retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    pub(crate) fn get_idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    pub(crate) fn get_actor(&self) -> Option<&str> {
+        self.actor.as_deref()
+    }
+
+    pub(crate) fn get_headers(&self) -> &BTreeMap<String, String> {
+        &self.headers
+    }
+}
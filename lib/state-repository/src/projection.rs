@@ -0,0 +1,198 @@
+use crate::checkpoint::CheckpointStore;
+use crate::consumer::{ConsumerHandle, Supervisor};
+use crate::error::RepositoryError;
+use crate::storage::{StoredEvent, SubscriptionStart};
+use crate::StateRepository;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+/// Read model built from the events of some types, whatever their stream, so that
+/// counts or listings do not replay the events on each query.
+///
+/// Events are delivered at least once: an event applied just before a crash is
+/// applied again on restart, its position not being saved yet.
+#[async_trait]
+pub trait Projection: Send + Sync + 'static {
+    /// Name of the projection, under which its checkpoints are saved.
+    fn name(&self) -> &str;
+
+    /// Types of the events the read model is built from, like `evt.account.Created`.
+    fn event_types(&self) -> Vec<String>;
+
+    /// Update the read model with an event, decoded with `StateRepository::decode_event`.
+    async fn apply(&self, repo: &StateRepository, event: &StoredEvent) -> anyhow::Result<()>;
+
+    /// Empty the read model, before it is rebuilt from the first events.
+    async fn reset(&self) -> anyhow::Result<()>;
+}
+
+pub type ProjectionHandle = JoinHandle<Result<(), RepositoryError>>;
+
+/// Feed projections from the event storage of a repository, saving their positions.
+#[derive(Clone)]
+pub struct ProjectionRunner {
+    repo: StateRepository,
+    checkpoints: Arc<dyn CheckpointStore>,
+}
+
+impl ProjectionRunner {
+    pub fn new<C>(repo: StateRepository, checkpoints: C) -> Self
+    where
+        C: CheckpointStore + 'static,
+    {
+        Self {
+            repo,
+            checkpoints: Arc::new(checkpoints),
+        }
+    }
+
+    /// Catch up from the checkpoints of the projection then follow the new events in a
+    /// spawned task, which ends on the first error.
+    ///
+    /// The subscriptions are opened before returning, so events appended afterwards are
+    /// always applied.
+    pub async fn start<P>(&self, projection: Arc<P>) -> Result<ProjectionHandle, RepositoryError>
+    where
+        P: Projection,
+    {
+        let mut subscriptions = Vec::new();
+
+        for event_type in projection.event_types() {
+            let checkpoint = self
+                .checkpoints
+                .load(projection.name(), &event_type)
+                .await
+                .map_err(RepositoryError::Checkpoint)?;
+
+            let subscription = self
+                .repo
+                .event_db()
+                .subscribe_event_type(&event_type, SubscriptionStart::from(checkpoint))
+                .await?;
+
+            subscriptions.push(subscription);
+        }
+
+        let runner = self.clone();
+
+        Ok(tokio::spawn(async move {
+            let mut events = stream::select_all(subscriptions);
+
+            while let Some(event) = events.next().await {
+                runner.apply(projection.as_ref(), &event?).await?;
+            }
+
+            Ok(())
+        }))
+    }
+
+    /// Follow each event type of the projection from its checkpoint in a task run by
+    /// `supervisor`, started again after a backoff when it fails, its status being kept
+    /// under the name of the projection.
+    ///
+    /// The subscriptions are opened by the tasks, so a storage unreachable at first is
+    /// retried as well.
+    pub fn supervise<P>(&self, supervisor: &Supervisor, projection: Arc<P>) -> Vec<ConsumerHandle>
+    where
+        P: Projection,
+    {
+        projection
+            .event_types()
+            .into_iter()
+            .map(|event_type| {
+                let runner = self.clone();
+                let status = supervisor.clone();
+                let projection = projection.clone();
+                let kind = event_type.clone();
+
+                supervisor.supervise(projection.name().to_string(), event_type, move || {
+                    let runner = runner.clone();
+                    let supervisor = status.clone();
+                    let projection = projection.clone();
+                    let event_type = kind.clone();
+
+                    async move {
+                        runner
+                            .follow(&supervisor, projection.as_ref(), &event_type)
+                            .await
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Empty the read model and forget its checkpoints, then start it from the first
+    /// events. A running task of the projection must be aborted first.
+    pub async fn rebuild<P>(&self, projection: Arc<P>) -> Result<ProjectionHandle, RepositoryError>
+    where
+        P: Projection,
+    {
+        projection
+            .reset()
+            .await
+            .map_err(RepositoryError::Projection)?;
+
+        self.checkpoints
+            .clear(projection.name())
+            .await
+            .map_err(RepositoryError::Checkpoint)?;
+
+        self.start(projection).await
+    }
+
+    /// Apply the events of a type from the checkpoint, counting them in the status.
+    async fn follow<P>(
+        &self,
+        supervisor: &Supervisor,
+        projection: &P,
+        event_type: &str,
+    ) -> Result<(), RepositoryError>
+    where
+        P: Projection,
+    {
+        let checkpoint = self
+            .checkpoints
+            .load(projection.name(), event_type)
+            .await
+            .map_err(RepositoryError::Checkpoint)?;
+
+        let mut subscription = self
+            .repo
+            .event_db()
+            .subscribe_event_type(event_type, SubscriptionStart::from(checkpoint))
+            .await?;
+
+        while let Some(event) = subscription.next().await {
+            let event = event?;
+            self.apply(projection, &event).await?;
+
+            supervisor.update(projection.name(), event_type, |status| {
+                status.record(1, 0, None);
+                status.acknowledge(event.position());
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn apply<P>(&self, projection: &P, event: &StoredEvent) -> Result<(), RepositoryError>
+    where
+        P: Projection,
+    {
+        projection
+            .apply(&self.repo, event)
+            .await
+            .map_err(RepositoryError::Projection)?;
+
+        if let Some(position) = event.position() {
+            self.checkpoints
+                .save(projection.name(), event.event_type(), position)
+                .await
+                .map_err(RepositoryError::Checkpoint)?;
+        }
+
+        Ok(())
+    }
+}
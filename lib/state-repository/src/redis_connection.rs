@@ -0,0 +1,31 @@
+use anyhow::{Context, Result};
+use redis::aio::ConnectionManager;
+use redis::Client;
+use tokio::sync::OnceCell;
+
+/// Single multiplexed Redis connection, opened on first use and reconnected when it
+/// drops.
+#[derive(Clone)]
+pub(crate) struct RedisConnection {
+    client: Client,
+    connection: OnceCell<ConnectionManager>,
+}
+
+impl RedisConnection {
+    pub(crate) fn new(client: Client) -> Self {
+        Self {
+            client,
+            connection: OnceCell::new(),
+        }
+    }
+
+    pub(crate) async fn get(&self) -> Result<ConnectionManager> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| self.client.get_tokio_connection_manager())
+            .await
+            .context("connect to cache db")?;
+
+        Ok(connection.clone())
+    }
+}
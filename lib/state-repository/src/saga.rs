@@ -0,0 +1,262 @@
+use crate::consumer::{ConsumerHandle, Supervisor};
+use crate::error::{CommandError, RepositoryError};
+use crate::metadata::Metadata;
+use crate::model_key::ModelKey;
+use crate::options::CommandOptions;
+use crate::schedule::Scheduler;
+use crate::storage::StoredEvent;
+use crate::waiter::ActionId;
+use crate::{StateRepository, EVENT_PREFIX};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use state::{EventName, State, StateName};
+use std::fmt;
+use tokio::time::Duration;
+use uuid::Uuid;
+
+type Apply = dyn FnOnce(
+        StateRepository,
+        Metadata,
+        CommandOptions,
+    ) -> BoxFuture<'static, Result<(), CommandError<String>>>
+    + Send;
+
+/// Command of a saga for an other state.
+pub struct Dispatch {
+    key: ModelKey,
+    state_name: StateName,
+    apply: Box<Apply>,
+}
+
+impl Dispatch {
+    pub fn new<S>(key: ModelKey, command: S::Command) -> Self
+    where
+        S: State + 'static,
+    {
+        let target = key.clone();
+
+        Self {
+            key,
+            state_name: S::name_prefix(),
+            apply: Box::new(move |repo, metadata, options| {
+                Box::pin(async move {
+                    repo.add_command_with_options::<S>(&target, command, Some(&metadata), &options)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| e.map_rejection(|e| e.to_string()))
+                })
+            }),
+        }
+    }
+
+    pub fn key(&self) -> &ModelKey {
+        &self.key
+    }
+    /// `State::name_prefix` of the state the command is for.
+    pub fn state_name(&self) -> StateName {
+        self.state_name
+    }
+}
+
+impl fmt::Debug for Dispatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dispatch")
+            .field("key", &self.key)
+            .field("state_name", &self.state_name)
+            .finish()
+    }
+}
+
+/// What an event of a saga asks for.
+#[derive(Debug)]
+pub enum SagaAction<C> {
+    /// Apply a command to an other state, then `on_rejected` to the saga if the state
+    /// refuses it, to compensate the steps already done.
+    Dispatch {
+        dispatch: Dispatch,
+        on_rejected: Option<C>,
+    },
+    /// Apply `command` to the saga after `delay`, unless cancelled before.
+    Timeout {
+        id: ActionId,
+        command: C,
+        delay: Duration,
+    },
+    /// Forget a pending timeout.
+    CancelTimeout { id: ActionId },
+}
+
+/// Workflow across states, with its own event-sourced state in a stream keyed by the
+/// correlation id of the events it follows.
+///
+/// The saga reacts to events of other states with commands of its own, and its events
+/// give the actions to take: commands for other states, compensated when refused, and
+/// timeouts. Every command of a saga carries the metadata of its cause, so that the
+/// answers of the other states keep the correlation id of the saga.
+#[async_trait]
+pub trait Saga: State + 'static {
+    /// Types of the events of other states the saga reacts to, like `evt.test-gold.paid`.
+    fn reacted_event_types() -> Vec<String>;
+
+    /// Events of the saga giving actions.
+    fn action_events() -> Vec<EventName>;
+
+    /// Command of the saga for an event of an other state carrying its correlation id,
+    /// `None` if the saga does not wait for it.
+    fn react(
+        &self,
+        repo: &StateRepository,
+        event: &StoredEvent,
+    ) -> Result<Option<Self::Command>, RepositoryError>;
+
+    /// Actions of an event of the saga stored in `saga_key`.
+    fn actions(event: Self::Event, saga_key: &ModelKey) -> Vec<SagaAction<Self::Command>>;
+
+    /// Stream of the saga following the events correlated by `correlation_id`.
+    fn saga_key(correlation_id: Uuid) -> ModelKey {
+        ModelKey::new(Self::name_prefix().to_string(), correlation_id.to_string())
+    }
+
+    /// Start a saga with a new correlation id, returning its key.
+    async fn start(
+        repo: &StateRepository,
+        command: Self::Command,
+    ) -> Result<ModelKey, CommandError<Self::Error>> {
+        let correlation_id = Uuid::new_v4();
+        let metadata = Metadata::new(None, correlation_id, correlation_id, false);
+        let key = Self::saga_key(correlation_id);
+
+        repo.add_command::<Self>(&key, command, Some(&metadata))
+            .await?;
+
+        Ok(key)
+    }
+
+    /// Follow the reacted events and take the actions of the saga events after the
+    /// checkpoints of the saga, the timeouts being applied by `scheduler`.
+    ///
+    /// Every command carries the id of the event causing it in its idempotency key, so an
+    /// event delivered again does not apply its commands twice. An event whose command
+    /// fails without compensation goes to the dead-letter stream. The events being
+    /// received by type, a timeout may be cancelled before it is scheduled, the store of
+    /// `scheduler` keeping the cancel for it, and the saga should refuse a command of a
+    /// step it left.
+    async fn process_saga(
+        supervisor: &Supervisor,
+        scheduler: Scheduler,
+    ) -> Result<Vec<ConsumerHandle>, RepositoryError> {
+        scheduler.register::<Self>();
+
+        let consumer = format!("{}.saga", Self::name_prefix());
+        let mut handles = Vec::new();
+
+        for event_type in Self::reacted_event_types() {
+            let handle = supervisor
+                .consume(consumer.clone(), event_type, |repo, e| async move {
+                    let metadata = e.metadata()?;
+                    let key = Self::saga_key(metadata.correlation_id());
+
+                    let saga = repo.get_model::<Self>(&key).await?;
+
+                    if let Some(command) = saga.state().react(&repo, &e)? {
+                        let options = CommandOptions::default().idempotency_key(e.id().to_string());
+
+                        repo.add_command_with_options::<Self>(
+                            &key,
+                            command,
+                            Some(&metadata),
+                            &options,
+                        )
+                        .await
+                        .map_err(CommandError::into_processing)?;
+                    }
+
+                    Ok(())
+                })
+                .await?;
+
+            handles.push(handle);
+        }
+
+        for event_name in Self::action_events() {
+            let event_type = format!("{}.{}.{}", EVENT_PREFIX, Self::name_prefix(), event_name);
+            let scheduler = scheduler.clone();
+
+            let handle = supervisor
+                .consume(consumer.clone(), event_type, move |repo, e| {
+                    let scheduler = scheduler.clone();
+
+                    async move {
+                        let metadata = e.metadata()?;
+                        let event = repo.decode_event::<Self::Event>(&e)?;
+                        let key = e.key();
+
+                        for (index, action) in Self::actions(event, &key).into_iter().enumerate() {
+                            let idempotency_key = format!("{}.{}", e.id(), index);
+
+                            take_action::<Self>(
+                                &repo,
+                                &scheduler,
+                                &key,
+                                &metadata,
+                                idempotency_key,
+                                action,
+                            )
+                            .await?;
+                        }
+
+                        Ok(())
+                    }
+                })
+                .await?;
+
+            handles.push(handle);
+        }
+
+        Ok(handles)
+    }
+}
+
+/// Take an action of an event of the saga stored in `key`, the commands carrying
+/// `idempotency_key`.
+async fn take_action<S>(
+    repo: &StateRepository,
+    scheduler: &Scheduler,
+    key: &ModelKey,
+    metadata: &Metadata,
+    idempotency_key: String,
+    action: SagaAction<S::Command>,
+) -> Result<(), RepositoryError>
+where
+    S: Saga,
+{
+    match action {
+        SagaAction::Dispatch {
+            dispatch,
+            on_rejected,
+        } => {
+            let options = CommandOptions::default().idempotency_key(idempotency_key.clone());
+
+            let applied = (dispatch.apply)(repo.clone(), metadata.clone(), options).await;
+
+            match (applied, on_rejected) {
+                (Err(CommandError::Rejected(_)), Some(command)) => {
+                    let options = CommandOptions::default()
+                        .idempotency_key(format!("{idempotency_key}.rejected"));
+
+                    repo.add_command_with_options::<S>(key, command, Some(metadata), &options)
+                        .await
+                        .map(|_| ())
+                        .map_err(CommandError::into_processing)
+                }
+                (applied, _) => applied.map_err(CommandError::into_processing),
+            }
+        }
+        SagaAction::Timeout { id, command, delay } => {
+            scheduler
+                .schedule::<S>(id, key, command, delay, Some(metadata))
+                .await
+        }
+        SagaAction::CancelTimeout { id } => scheduler.cancel(id).await.map(|_| ()),
+    }
+}
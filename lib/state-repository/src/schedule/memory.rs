@@ -0,0 +1,93 @@
+use crate::schedule::{ScheduleStore, ScheduledCommand};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Scheduled commands living in the process memory, shared between its clones.
+#[derive(Clone, Default)]
+pub struct InMemoryScheduleStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    commands: HashMap<Uuid, ScheduledCommand>,
+    /// Due time of the commands rescheduled before being added, `None` when removed.
+    unscheduled: HashMap<Uuid, Option<SystemTime>>,
+}
+
+impl InMemoryScheduleStore {
+    fn inner(&self) -> Result<MutexGuard<'_, Inner>> {
+        self.inner
+            .lock()
+            .map_err(|e| anyhow!("lock memory schedule : {e}"))
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for InMemoryScheduleStore {
+    async fn add(&self, mut command: ScheduledCommand) -> Result<()> {
+        let mut inner = self.inner()?;
+
+        match inner.unscheduled.get(&command.id) {
+            Some(None) => return Ok(()),
+            Some(Some(due)) => command.due = *due,
+            None => {}
+        }
+
+        inner.commands.entry(command.id).or_insert(command);
+
+        Ok(())
+    }
+
+    async fn due(&self, now: SystemTime) -> Result<Vec<ScheduledCommand>> {
+        let inner = self.inner()?;
+
+        let mut due: Vec<ScheduledCommand> = inner
+            .commands
+            .values()
+            .filter(|command| command.due <= now)
+            .cloned()
+            .collect();
+        due.sort_by_key(|command| command.due);
+
+        Ok(due)
+    }
+
+    async fn reschedule(&self, id: Uuid, due: SystemTime) -> Result<bool> {
+        let mut inner = self.inner()?;
+
+        if let Some(command) = inner.commands.get_mut(&id) {
+            command.due = due;
+            return Ok(true);
+        }
+
+        // a removed command stays removed
+        inner
+            .unscheduled
+            .entry(id)
+            .and_modify(|unscheduled| {
+                if unscheduled.is_some() {
+                    *unscheduled = Some(due);
+                }
+            })
+            .or_insert(Some(due));
+
+        Ok(false)
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<bool> {
+        let mut inner = self.inner()?;
+
+        if inner.commands.remove(&id).is_some() {
+            return Ok(true);
+        }
+
+        inner.unscheduled.insert(id, None);
+
+        Ok(false)
+    }
+}
@@ -0,0 +1,338 @@
+mod memory;
+mod sql;
+
+pub use memory::InMemoryScheduleStore;
+pub use sql::SqlScheduleStore;
+
+use crate::clock::{Clock, SystemClock};
+use crate::consumer::{ConsumerHandle, Supervisor};
+use crate::dead_letter::{self, DeadLetter};
+use crate::error::{CommandError, RepositoryError};
+use crate::metadata::Metadata;
+use crate::model_key::ModelKey;
+use crate::options::CommandOptions;
+use crate::StateRepository;
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use state::State;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Consumer under which a started scheduler has its status, and under which the
+/// commands it cannot apply are sent to the dead letters, to inspect or discard.
+pub const SCHEDULER_CONSUMER: &str = "scheduler";
+const SCHEDULER_EVENT_TYPE: &str = "scheduled";
+
+/// Command to apply to a state once its due time is reached.
+#[derive(Clone, Debug)]
+pub struct ScheduledCommand {
+    id: Uuid,
+    due: SystemTime,
+    key: ModelKey,
+    state_name: String,
+    command: Value,
+    metadata: Option<Metadata>,
+}
+
+impl ScheduledCommand {
+    /// Command as kept by a store, `command` being the json of a command of the state
+    /// named `state_name`.
+    pub fn new(
+        id: Uuid,
+        due: SystemTime,
+        key: ModelKey,
+        state_name: impl Into<String>,
+        command: Value,
+        metadata: Option<Metadata>,
+    ) -> Self {
+        Self {
+            id,
+            due,
+            key,
+            state_name: state_name.into(),
+            command,
+            metadata,
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+    pub fn due(&self) -> SystemTime {
+        self.due
+    }
+    pub fn key(&self) -> &ModelKey {
+        &self.key
+    }
+    /// `State::name_prefix` of the state the command is for.
+    pub fn state_name(&self) -> &str {
+        &self.state_name
+    }
+    pub fn command(&self) -> &Value {
+        &self.command
+    }
+    /// Metadata of the event causing the command.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+}
+
+/// Durable store of the commands waiting for their due time.
+///
+/// A command can be rescheduled or removed before it is added, the actions on a
+/// command being received in any order: the store keeps the action, and applies it to
+/// the command added later with its id.
+#[async_trait]
+pub trait ScheduleStore: Send + Sync {
+    /// Keep a command until it is removed, a command with the same id being kept only once.
+    /// A command removed before is not kept, and one rescheduled before takes its new due
+    /// time.
+    async fn add(&self, command: ScheduledCommand) -> anyhow::Result<()>;
+
+    /// Commands due at `now`, the earliest first.
+    async fn due(&self, now: SystemTime) -> anyhow::Result<Vec<ScheduledCommand>>;
+
+    /// Move the due time of a command, telling whether it was kept. The due time of a
+    /// command not kept is kept for when it is added.
+    async fn reschedule(&self, id: Uuid, due: SystemTime) -> anyhow::Result<bool>;
+
+    /// Forget a command, telling whether it was kept. A command not kept is not kept
+    /// either when it is added.
+    async fn remove(&self, id: Uuid) -> anyhow::Result<bool>;
+}
+
+type Fire = dyn Fn(StateRepository, ScheduledCommand) -> BoxFuture<'static, Result<(), CommandError<String>>>
+    + Send
+    + Sync;
+
+/// Apply the scheduled commands through `StateRepository::add_command` once they are due,
+/// polling its store.
+///
+/// A command is removed from the store after being applied, with its id as idempotency
+/// key, so that a command applied again after a crash is only applied once.
+#[derive(Clone)]
+pub struct Scheduler {
+    repo: StateRepository,
+    store: Arc<dyn ScheduleStore>,
+    clock: Arc<dyn Clock>,
+    poll_interval: Duration,
+    states: Arc<RwLock<HashMap<String, Arc<Fire>>>>,
+}
+
+impl Scheduler {
+    pub fn new<S>(repo: StateRepository, store: S) -> Self
+    where
+        S: ScheduleStore + 'static,
+    {
+        Self {
+            repo,
+            store: Arc::new(store),
+            clock: Arc::new(SystemClock),
+            poll_interval: POLL_INTERVAL,
+            states: Arc::default(),
+        }
+    }
+
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Allow the commands of a state to be applied, its name being all the store keeps.
+    pub fn register<S>(&self)
+    where
+        S: State + 'static,
+    {
+        let fire: Arc<Fire> = Arc::new(|repo, scheduled| {
+            Box::pin(async move {
+                let command: S::Command = serde_json::from_value(scheduled.command)
+                    .context("decode scheduled command")
+                    .map_err(RepositoryError::Deserialization)?;
+
+                let options = CommandOptions::default().idempotency_key(scheduled.id.to_string());
+
+                repo.add_command_with_options::<S>(
+                    &scheduled.key,
+                    command,
+                    scheduled.metadata.as_ref(),
+                    &options,
+                )
+                .await
+                .map(|_| ())
+                .map_err(|e| e.map_rejection(|e| e.to_string()))
+            })
+        });
+
+        self.states
+            .write()
+            .unwrap()
+            .insert(S::name_prefix().to_string(), fire);
+    }
+
+    /// Apply `command` to the state of `key` after `delay`, scheduling an `id` already
+    /// scheduled being ignored. An `id` cancelled or rescheduled before is cancelled or
+    /// rescheduled.
+    pub async fn schedule<S>(
+        &self,
+        id: Uuid,
+        key: &ModelKey,
+        command: S::Command,
+        delay: Duration,
+        previous_metadata: Option<&Metadata>,
+    ) -> Result<(), RepositoryError>
+    where
+        S: State + 'static,
+    {
+        self.register::<S>();
+
+        let scheduled = ScheduledCommand {
+            id,
+            due: self.clock.now() + delay,
+            key: key.clone(),
+            state_name: S::name_prefix().to_string(),
+            command: serde_json::to_value(command)?,
+            metadata: previous_metadata.cloned(),
+        };
+
+        self.store
+            .add(scheduled)
+            .await
+            .map_err(RepositoryError::Schedule)
+    }
+
+    /// Forget a command before it is applied, telling whether it was still scheduled. A
+    /// command not scheduled yet is not applied once scheduled.
+    pub async fn cancel(&self, id: Uuid) -> Result<bool, RepositoryError> {
+        self.store
+            .remove(id)
+            .await
+            .map_err(RepositoryError::Schedule)
+    }
+
+    /// Apply a command still scheduled `delay` from now instead, telling whether it was
+    /// still scheduled. A command not scheduled yet is applied `delay` from now once
+    /// scheduled.
+    pub async fn reschedule(&self, id: Uuid, delay: Duration) -> Result<bool, RepositoryError> {
+        self.store
+            .reschedule(id, self.clock.now() + delay)
+            .await
+            .map_err(RepositoryError::Schedule)
+    }
+
+    /// Apply the commands due now, returning how many were applied.
+    ///
+    /// A command refused by its state is removed, as it would always be refused, and a
+    /// command failing otherwise is sent to the dead letters of `SCHEDULER_CONSUMER`
+    /// then removed. A command failing with a transient error is left for a later tick,
+    /// the following ones being still applied. The commands of states not registered are
+    /// left in the store.
+    pub async fn tick(&self) -> Result<usize, RepositoryError> {
+        Ok(self.apply_due().await?.applied)
+    }
+
+    async fn apply_due(&self) -> Result<Tick, RepositoryError> {
+        let due = self
+            .store
+            .due(self.clock.now())
+            .await
+            .map_err(RepositoryError::Schedule)?;
+
+        let mut tick = Tick::default();
+
+        for scheduled in due {
+            let id = scheduled.id;
+            let fire = self
+                .states
+                .read()
+                .unwrap()
+                .get(&scheduled.state_name)
+                .cloned();
+
+            let fire = match fire {
+                Some(fire) => fire,
+                // left for a scheduler knowing the state
+                None => continue,
+            };
+
+            match fire(self.repo.clone(), scheduled.clone()).await {
+                Ok(()) => tick.applied += 1,
+                Err(CommandError::Rejected(_)) => {}
+                Err(CommandError::Repository(e)) if e.is_transient() => {
+                    tick.last_error = Some(e);
+                    continue;
+                }
+                Err(CommandError::Repository(e)) => {
+                    let dead_letter = DeadLetter::scheduled(SCHEDULER_CONSUMER, &scheduled, &e);
+                    dead_letter::send(&self.repo, dead_letter, scheduled.metadata()).await?;
+
+                    tick.dead_letters += 1;
+                    tick.last_error = Some(e);
+                }
+            }
+
+            self.cancel(id).await?;
+        }
+
+        Ok(tick)
+    }
+
+    /// Apply the due commands every poll interval in a task supervised by `supervisor`,
+    /// under `SCHEDULER_CONSUMER`, polling again after a backoff when the stores fail.
+    pub fn start(&self, supervisor: &Supervisor) -> ConsumerHandle {
+        let scheduler = self.clone();
+        let status = supervisor.clone();
+
+        supervisor.supervise(
+            SCHEDULER_CONSUMER.to_string(),
+            SCHEDULER_EVENT_TYPE.to_string(),
+            move || {
+                let scheduler = scheduler.clone();
+                let status = status.clone();
+
+                async move {
+                    loop {
+                        let tick = scheduler.apply_due().await?;
+
+                        status.update(SCHEDULER_CONSUMER, SCHEDULER_EVENT_TYPE, |status| {
+                            status.record(
+                                tick.applied + tick.dead_letters,
+                                tick.dead_letters,
+                                tick.last_error.as_ref(),
+                            );
+                        });
+
+                        sleep(scheduler.poll_interval).await;
+                    }
+                }
+            },
+        )
+    }
+}
+
+/// What a tick did with the due commands.
+#[derive(Debug, Default)]
+struct Tick {
+    applied: usize,
+    dead_letters: usize,
+    /// Last error of a command, kept in the store when transient.
+    last_error: Option<RepositoryError>,
+}
@@ -0,0 +1,230 @@
+use crate::metadata::Metadata;
+use crate::model_key::ModelKey;
+use crate::schedule::{ScheduleStore, ScheduledCommand};
+use crate::storage::{is_unique_violation, SqlStorage};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Scheduled commands kept in the `scheduled_commands` table next to the events, the
+/// tables being created by `SqlStorage::migrate`. The commands rescheduled or removed
+/// before being added are kept in `unscheduled_actions`, with a null due time when removed.
+#[derive(Clone)]
+pub struct SqlScheduleStore {
+    storage: SqlStorage,
+}
+
+impl SqlScheduleStore {
+    pub fn new(storage: SqlStorage) -> Self {
+        Self { storage }
+    }
+
+    /// Action kept for a command before it was added, with the due time it was
+    /// rescheduled to or `None` when removed.
+    async fn unscheduled(&self, id: Uuid) -> Result<Option<Option<i64>>> {
+        let query = self
+            .storage
+            .query("SELECT due FROM unscheduled_actions WHERE id = ?");
+
+        let row = sqlx::query(&query)
+            .bind(id.to_string())
+            .fetch_optional(self.storage.pool())
+            .await
+            .context("read unscheduled action")?;
+
+        row.map(|row| row.try_get("due"))
+            .transpose()
+            .context("decode unscheduled action")
+    }
+
+    /// Keep an action for a command not added yet, a removal being kept over a
+    /// reschedule.
+    async fn keep_unscheduled(&self, id: Uuid, due: Option<i64>) -> Result<()> {
+        let query = self
+            .storage
+            .query("INSERT INTO unscheduled_actions (id, due) VALUES (?, ?)");
+
+        let inserted = sqlx::query(&query)
+            .bind(id.to_string())
+            .bind(due)
+            .execute(self.storage.pool())
+            .await;
+
+        match inserted {
+            Ok(_) => return Ok(()),
+            Err(err) if is_unique_violation(&err) => {}
+            Err(err) => return Err(err).context("insert unscheduled action"),
+        }
+
+        let query = self
+            .storage
+            .query("UPDATE unscheduled_actions SET due = ? WHERE id = ? AND due IS NOT NULL");
+
+        sqlx::query(&query)
+            .bind(due)
+            .bind(id.to_string())
+            .execute(self.storage.pool())
+            .await
+            .context("update unscheduled action")?;
+
+        Ok(())
+    }
+
+    async fn update_due(&self, id: Uuid, due: i64) -> Result<bool> {
+        let query = self
+            .storage
+            .query("UPDATE scheduled_commands SET due = ? WHERE id = ?");
+
+        let updated = sqlx::query(&query)
+            .bind(due)
+            .bind(id.to_string())
+            .execute(self.storage.pool())
+            .await
+            .context("reschedule command")?;
+
+        Ok(updated.rows_affected() > 0)
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<bool> {
+        let query = self
+            .storage
+            .query("DELETE FROM scheduled_commands WHERE id = ?");
+
+        let deleted = sqlx::query(&query)
+            .bind(id.to_string())
+            .execute(self.storage.pool())
+            .await
+            .context("delete scheduled command")?;
+
+        Ok(deleted.rows_affected() > 0)
+    }
+}
+
+/// Due times are kept as milliseconds since the epoch.
+fn to_millis(time: SystemTime) -> Result<i64> {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .context("due time before the epoch")?
+        .as_millis();
+    Ok(millis as i64)
+}
+
+fn scheduled_command(row: &AnyRow) -> Result<ScheduledCommand> {
+    let id: String = row.try_get("id")?;
+    let due: i64 = row.try_get("due")?;
+    let command: String = row.try_get("command")?;
+    let metadata: Option<String> = row.try_get("metadata")?;
+    let metadata_id: Option<String> = row.try_get("metadata_id")?;
+
+    let metadata = metadata
+        .map(|metadata| -> Result<Metadata> {
+            let mut metadata: Metadata = serde_json::from_str(&metadata)?;
+            let id = metadata_id.map(|id| id.parse()).transpose()?;
+            metadata.set_id(id);
+            Ok(metadata)
+        })
+        .transpose()
+        .context("decode scheduled metadata")?;
+
+    Ok(ScheduledCommand {
+        id: id.parse().context("decode scheduled id")?,
+        due: UNIX_EPOCH + Duration::from_millis(due as u64),
+        key: ModelKey::new(row.try_get("stream_name")?, row.try_get("stream_id")?),
+        state_name: row.try_get("state_name")?,
+        command: serde_json::from_str(&command).context("decode scheduled command")?,
+        metadata,
+    })
+}
+
+#[async_trait]
+impl ScheduleStore for SqlScheduleStore {
+    async fn add(&self, command: ScheduledCommand) -> Result<()> {
+        let query = self.storage.query(
+            "INSERT INTO scheduled_commands \
+             (id, due, stream_name, stream_id, state_name, command, metadata, metadata_id) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        );
+
+        let metadata = command
+            .metadata
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let metadata_id = command
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.id())
+            .map(|id| id.to_string());
+
+        let inserted = sqlx::query(&query)
+            .bind(command.id.to_string())
+            .bind(to_millis(command.due)?)
+            .bind(command.key.stream_name())
+            .bind(command.key.stream_id())
+            .bind(&command.state_name)
+            .bind(command.command.to_string())
+            .bind(metadata)
+            .bind(metadata_id)
+            .execute(self.storage.pool())
+            .await;
+
+        match inserted {
+            Ok(_) => {}
+            // already scheduled
+            Err(err) if is_unique_violation(&err) => return Ok(()),
+            Err(err) => return Err(err).context("insert scheduled command"),
+        }
+
+        // an action kept before the insert is applied here, one kept after it by the
+        // action itself once it finds the command
+        match self.unscheduled(command.id).await? {
+            Some(Some(due)) => self.update_due(command.id, due).await.map(|_| ()),
+            Some(None) => self.delete(command.id).await.map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    async fn due(&self, now: SystemTime) -> Result<Vec<ScheduledCommand>> {
+        let query = self.storage.query(
+            "SELECT id, due, stream_name, stream_id, state_name, command, metadata, metadata_id \
+             FROM scheduled_commands WHERE due <= ? ORDER BY due",
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(to_millis(now)?)
+            .fetch_all(self.storage.pool())
+            .await
+            .context("read scheduled commands")?;
+
+        rows.iter().map(scheduled_command).collect()
+    }
+
+    async fn reschedule(&self, id: Uuid, due: SystemTime) -> Result<bool> {
+        let due = to_millis(due)?;
+
+        if self.update_due(id, due).await? {
+            return Ok(true);
+        }
+
+        // the command may be added while the action is kept
+        self.keep_unscheduled(id, Some(due)).await?;
+        self.update_due(id, due).await?;
+
+        Ok(false)
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<bool> {
+        if self.delete(id).await? {
+            return Ok(true);
+        }
+
+        // the command may be added while the action is kept
+        self.keep_unscheduled(id, None).await?;
+        self.delete(id).await?;
+
+        Ok(false)
+    }
+}
@@ -0,0 +1,67 @@
+use crate::error::RepositoryError;
+use crate::metadata::EventWithMetadata;
+use crate::model_key::ModelKey;
+use crate::snapshot::{Snapshot, SnapshotStore};
+use crate::storage::EventStorage;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+const SNAPSHOT_SUFFIX: &str = "snapshot";
+
+/// Snapshots appended to a `{stream name}-snapshot` stream next to the stream they
+/// come from, the last one being the only one read.
+#[derive(Clone)]
+pub struct EventSnapshotStore<E> {
+    event_db: E,
+}
+
+impl<E: EventStorage> EventSnapshotStore<E> {
+    pub fn new(event_db: E) -> Self {
+        Self { event_db }
+    }
+
+    fn snapshot_key(key: &ModelKey) -> ModelKey {
+        ModelKey::new(
+            format!("{}-{}", key.stream_name(), SNAPSHOT_SUFFIX),
+            key.stream_id().to_string(),
+        )
+    }
+}
+
+#[async_trait]
+impl<E: EventStorage> SnapshotStore for EventSnapshotStore<E> {
+    async fn load(&self, key: &ModelKey) -> Result<Option<Snapshot>> {
+        let last = self
+            .event_db
+            .read_last_event(&Self::snapshot_key(key))
+            .await?;
+
+        last.map(|event| event.as_json().context("decode snapshot"))
+            .transpose()
+    }
+
+    async fn save(&self, key: &ModelKey, snapshot: Snapshot) -> Result<()> {
+        let snapshot_key = Self::snapshot_key(key);
+
+        let last_revision = self
+            .event_db
+            .read_last_event(&snapshot_key)
+            .await?
+            .map(|event| event.revision());
+
+        let event = EventWithMetadata::from_snapshot(
+            format!("{}.{}", SNAPSHOT_SUFFIX, key.stream_name()),
+            serde_json::to_value(snapshot).context("encode snapshot")?,
+        );
+
+        match self
+            .event_db
+            .append_events(&snapshot_key, last_revision, vec![event])
+            .await
+        {
+            // an other snapshot has been taken meanwhile
+            Err(RepositoryError::Conflict(_)) => Ok(()),
+            appended => Ok(appended?),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use crate::model_key::ModelKey;
+use crate::snapshot::{Snapshot, SnapshotStore};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Snapshots living in the process memory, shared between its clones.
+#[derive(Clone, Default)]
+pub struct InMemorySnapshotStore {
+    snapshots: Arc<Mutex<HashMap<String, Snapshot>>>,
+}
+
+#[async_trait]
+impl SnapshotStore for InMemorySnapshotStore {
+    async fn load(&self, key: &ModelKey) -> Result<Option<Snapshot>> {
+        let snapshots = self
+            .snapshots
+            .lock()
+            .map_err(|e| anyhow!("lock memory snapshots : {e}"))?;
+
+        Ok(snapshots.get(&key.format()).cloned())
+    }
+
+    async fn save(&self, key: &ModelKey, snapshot: Snapshot) -> Result<()> {
+        let mut snapshots = self
+            .snapshots
+            .lock()
+            .map_err(|e| anyhow!("lock memory snapshots : {e}"))?;
+
+        snapshots.insert(key.format(), snapshot);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+mod event_db;
+mod memory;
+mod redis_store;
+
+pub use event_db::EventSnapshotStore;
+pub use memory::InMemorySnapshotStore;
+pub use redis_store::RedisSnapshotStore;
+
+use crate::model_key::ModelKey;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// State of a stream at a revision, to avoid replaying the whole stream.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Snapshot {
+    version: u32,
+    revision: u64,
+    state: Value,
+}
+
+impl Snapshot {
+    pub fn new<S: Serialize>(version: u32, revision: u64, state: &S) -> serde_json::Result<Self> {
+        Ok(Self {
+            version,
+            revision,
+            state: serde_json::to_value(state)?,
+        })
+    }
+
+    /// `State::snapshot_version` of the state when the snapshot was taken.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Revision of the last event played on the state.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn state<S: DeserializeOwned>(&self) -> serde_json::Result<S> {
+        S::deserialize(&self.state)
+    }
+}
+
+/// Store keeping the last snapshot of each stream.
+#[async_trait]
+pub trait SnapshotStore: Send + Sync {
+    async fn load(&self, key: &ModelKey) -> Result<Option<Snapshot>>;
+
+    async fn save(&self, key: &ModelKey, snapshot: Snapshot) -> Result<()>;
+}
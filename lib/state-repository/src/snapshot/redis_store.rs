@@ -0,0 +1,48 @@
+use crate::model_key::ModelKey;
+use crate::redis_connection::RedisConnection;
+use crate::snapshot::{Snapshot, SnapshotStore};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use redis::{AsyncCommands, Client};
+
+/// Snapshots kept in Redis through a single multiplexed connection.
+#[derive(Clone)]
+pub struct RedisSnapshotStore {
+    connection: RedisConnection,
+}
+
+impl RedisSnapshotStore {
+    pub fn new(client: Client) -> Self {
+        Self {
+            connection: RedisConnection::new(client),
+        }
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for RedisSnapshotStore {
+    async fn load(&self, key: &ModelKey) -> Result<Option<Snapshot>> {
+        let value: Option<String> = self
+            .connection
+            .get()
+            .await?
+            .get(key.format())
+            .await
+            .context("get snapshot")?;
+
+        value
+            .map(|value| serde_json::from_str(&value).context("decode snapshot"))
+            .transpose()
+    }
+
+    async fn save(&self, key: &ModelKey, snapshot: Snapshot) -> Result<()> {
+        let value = serde_json::to_string(&snapshot).context("encode snapshot")?;
+
+        self.connection
+            .get()
+            .await?
+            .set(key.format(), value)
+            .await
+            .context("set snapshot")
+    }
+}
@@ -0,0 +1,248 @@
+use crate::error::RepositoryError;
+use crate::metadata::EventWithMetadata;
+use crate::model_key::ModelKey;
+use crate::storage::{EventStorage, EventSubscription, StoredEvent, SubscriptionStart};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use eventstore::{
+    AppendToStreamOptions, Client, Error, EventData, ExpectedRevision, ReadStream,
+    ReadStreamOptions, ResolvedEvent, StreamPosition, SubscribeToStreamOptions,
+};
+use futures::stream;
+use uuid::Uuid;
+
+fn event_type_stream(event_type: &str) -> String {
+    format!("$et-{event_type}")
+}
+
+/// Stream of the `$by_correlation_id` system projection, which must be enabled.
+fn correlation_stream(correlation_id: Uuid) -> String {
+    format!("$bc-{correlation_id}")
+}
+
+/// The position of an event read by type is the revision of its link in `$et-{type}`.
+fn stored_event(resolved: ResolvedEvent) -> Option<StoredEvent> {
+    let position = resolved.link.map(|link| link.revision);
+
+    resolved.event.map(|recorded| StoredEvent {
+        stream_id: recorded.stream_id,
+        id: recorded.id,
+        revision: recorded.revision,
+        position,
+        event_type: recorded.event_type,
+        data: recorded.data.to_vec(),
+        custom_metadata: recorded.custom_metadata.to_vec(),
+        created: Some(recorded.created.into()),
+    })
+}
+
+/// Next event of a stream being read, none at its end or when it does not exist yet.
+async fn next_event(stream: &mut ReadStream) -> Result<Option<ResolvedEvent>, RepositoryError> {
+    match stream.next().await {
+        Ok(resolved) => Ok(resolved),
+        Err(Error::ResourceNotFound) => Ok(None),
+        Err(err) => Err(RepositoryError::StoreUnavailable(anyhow!(
+            "read stream : {:?}",
+            err
+        ))),
+    }
+}
+
+fn event_data(event: EventWithMetadata) -> Result<EventData, RepositoryError> {
+    let event_data = EventData::json(event.event_type(), event.data())?
+        .id(event.id())
+        .metadata_as_json(event.metadata())?;
+
+    Ok(event_data)
+}
+
+#[async_trait]
+impl EventStorage for Client {
+    async fn read_events(
+        &self,
+        key: &ModelKey,
+        from_revision: Option<u64>,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let options = ReadStreamOptions::default();
+        let options = if let Some(revision) = from_revision {
+            options.position(StreamPosition::Position(revision))
+        } else {
+            options.position(StreamPosition::Start)
+        };
+
+        let mut stream = self
+            .read_stream(key.format(), &options)
+            .await
+            .context("connect to event db")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        let mut events = Vec::new();
+
+        while let Some(resolved) = next_event(&mut stream).await? {
+            events.extend(stored_event(resolved));
+        }
+
+        Ok(events)
+    }
+
+    async fn read_last_event(
+        &self,
+        key: &ModelKey,
+    ) -> Result<Option<StoredEvent>, RepositoryError> {
+        let options = ReadStreamOptions::default()
+            .position(StreamPosition::End)
+            .backwards()
+            .max_count(1);
+
+        let mut stream = self
+            .read_stream(key.format(), &options)
+            .await
+            .context("connect to event db")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        Ok(next_event(&mut stream).await?.and_then(stored_event))
+    }
+
+    /// Event Store DB has no index on the metadata, the stream is read until the key.
+    async fn read_idempotency_key(
+        &self,
+        key: &ModelKey,
+        idempotency_key: &str,
+    ) -> Result<Option<StoredEvent>, RepositoryError> {
+        let options = ReadStreamOptions::default().position(StreamPosition::Start);
+
+        let mut stream = self
+            .read_stream(key.format(), &options)
+            .await
+            .context("connect to event db")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        while let Some(resolved) = next_event(&mut stream).await? {
+            if let Some(event) = stored_event(resolved) {
+                if event.metadata()?.idempotency_key() == Some(idempotency_key) {
+                    return Ok(Some(event));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn append_events(
+        &self,
+        key: &ModelKey,
+        expected_revision: Option<u64>,
+        events: Vec<EventWithMetadata>,
+    ) -> Result<(), RepositoryError> {
+        let options = if let Some(revision) = expected_revision {
+            AppendToStreamOptions::default().expected_revision(ExpectedRevision::Exact(revision))
+        } else {
+            AppendToStreamOptions::default().expected_revision(ExpectedRevision::NoStream)
+        };
+
+        let events = events
+            .into_iter()
+            .map(event_data)
+            .collect::<Result<Vec<EventData>, RepositoryError>>()?;
+
+        let appended = self.append_to_stream(key.format(), &options, events).await;
+
+        match appended {
+            Ok(_) => Ok(()),
+            Err(Error::WrongExpectedVersion { expected, current }) => Err(
+                RepositoryError::Conflict(format!("{current} instead of {expected}")),
+            ),
+            Err(err) => Err(RepositoryError::StoreUnavailable(anyhow!(
+                "error while appending : {:?}",
+                err
+            ))),
+        }
+    }
+
+    async fn read_event_type(&self, event_type: &str) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let options = ReadStreamOptions::default()
+            .position(StreamPosition::Start)
+            .resolve_link_tos();
+
+        let mut stream = self
+            .read_stream(event_type_stream(event_type), &options)
+            .await
+            .context("connect to event db")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        let mut events = Vec::new();
+
+        while let Some(resolved) = next_event(&mut stream).await? {
+            events.extend(stored_event(resolved));
+        }
+
+        Ok(events)
+    }
+
+    async fn read_correlation(
+        &self,
+        correlation_id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let options = ReadStreamOptions::default()
+            .position(StreamPosition::Start)
+            .resolve_link_tos();
+
+        let mut stream = self
+            .read_stream(correlation_stream(correlation_id), &options)
+            .await
+            .context("connect to event db")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        let mut events = Vec::new();
+
+        // the position of a link in `$bc-` is not the one of its event type
+        while let Some(resolved) = next_event(&mut stream).await? {
+            events.extend(stored_event(resolved).map(|event| StoredEvent {
+                position: None,
+                ..event
+            }));
+        }
+
+        Ok(events)
+    }
+
+    async fn subscribe_event_type(
+        &self,
+        event_type: &str,
+        start: SubscriptionStart,
+    ) -> Result<EventSubscription, RepositoryError> {
+        let start = match start {
+            SubscriptionStart::Beginning => StreamPosition::Start,
+            SubscriptionStart::End => StreamPosition::End,
+            SubscriptionStart::After(position) => StreamPosition::Position(position),
+        };
+
+        let options = SubscribeToStreamOptions::default()
+            .start_from(start)
+            .resolve_link_tos();
+
+        let subscription = self
+            .subscribe_to_stream(event_type_stream(event_type), &options)
+            .await;
+
+        let events = stream::unfold(Some(subscription), |subscription| async move {
+            let mut subscription = subscription?;
+            loop {
+                match subscription.next().await {
+                    Ok(resolved) => {
+                        if let Some(event) = stored_event(resolved) {
+                            return Some((Ok(event), Some(subscription)));
+                        }
+                    }
+                    Err(err) => {
+                        let err =
+                            RepositoryError::StoreUnavailable(anyhow!("subscription : {:?}", err));
+                        return Some((Err(err), None));
+                    }
+                }
+            }
+        });
+
+        Ok(Box::pin(events))
+    }
+}
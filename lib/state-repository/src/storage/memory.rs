@@ -0,0 +1,217 @@
+use crate::error::RepositoryError;
+use crate::metadata::EventWithMetadata;
+use crate::model_key::ModelKey;
+use crate::storage::{EventStorage, EventSubscription, StoredEvent, SubscriptionStart};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use uuid::Uuid;
+
+/// Event storage living in the process memory, shared between its clones.
+///
+/// It behaves like EventStoreDB with the `$by_event_type` projection: appends check
+/// the expected revision, and events can be read or subscribed to by type, or read by
+/// correlation id.
+#[derive(Clone, Default)]
+pub struct InMemoryStorage {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    streams: HashMap<String, Vec<StoredEvent>>,
+    event_types: HashMap<String, Vec<StoredEvent>>,
+    correlations: HashMap<Uuid, Vec<StoredEvent>>,
+    idempotency_keys: HashMap<(String, String), StoredEvent>,
+    subscribers: Vec<(String, UnboundedSender<StoredEvent>)>,
+}
+
+impl InMemoryStorage {
+    fn inner(&self) -> Result<MutexGuard<'_, Inner>, RepositoryError> {
+        self.inner
+            .lock()
+            .map_err(|e| RepositoryError::StoreUnavailable(anyhow!("lock memory storage : {e}")))
+    }
+}
+
+#[async_trait]
+impl EventStorage for InMemoryStorage {
+    async fn read_events(
+        &self,
+        key: &ModelKey,
+        from_revision: Option<u64>,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let inner = self.inner()?;
+
+        let events = inner
+            .streams
+            .get(&key.format())
+            .map(|events| {
+                events
+                    .iter()
+                    .skip(from_revision.unwrap_or_default() as usize)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(events)
+    }
+
+    async fn read_last_event(
+        &self,
+        key: &ModelKey,
+    ) -> Result<Option<StoredEvent>, RepositoryError> {
+        let inner = self.inner()?;
+
+        Ok(inner
+            .streams
+            .get(&key.format())
+            .and_then(|events| events.last())
+            .cloned())
+    }
+
+    async fn read_idempotency_key(
+        &self,
+        key: &ModelKey,
+        idempotency_key: &str,
+    ) -> Result<Option<StoredEvent>, RepositoryError> {
+        let inner = self.inner()?;
+
+        Ok(inner
+            .idempotency_keys
+            .get(&(key.format(), idempotency_key.to_string()))
+            .cloned())
+    }
+
+    async fn append_events(
+        &self,
+        key: &ModelKey,
+        expected_revision: Option<u64>,
+        events: Vec<EventWithMetadata>,
+    ) -> Result<(), RepositoryError> {
+        let mut inner = self.inner()?;
+        let Inner {
+            streams,
+            event_types,
+            correlations,
+            idempotency_keys,
+            subscribers,
+        } = &mut *inner;
+
+        let stream_id = key.format();
+        let stream = streams.entry(stream_id.clone()).or_default();
+
+        let current_revision = stream.last().map(|event| event.revision);
+        if current_revision != expected_revision {
+            return Err(RepositoryError::Conflict(format!(
+                "{current_revision:?} instead of {expected_revision:?}"
+            )));
+        }
+
+        for event in events {
+            let stored_event = StoredEvent {
+                stream_id: stream_id.clone(),
+                id: event.id(),
+                revision: stream.len() as u64,
+                position: None,
+                event_type: event.event_type().to_string(),
+                data: serde_json::to_vec(event.data())?,
+                custom_metadata: serde_json::to_vec(event.metadata())?,
+                created: Some(SystemTime::now()),
+            };
+
+            let typed_events = event_types
+                .entry(stored_event.event_type.clone())
+                .or_default();
+
+            let typed_event = StoredEvent {
+                position: Some(typed_events.len() as u64),
+                ..stored_event.clone()
+            };
+
+            subscribers.retain(|(event_type, subscriber)| {
+                event_type != &typed_event.event_type
+                    || subscriber.send(typed_event.clone()).is_ok()
+            });
+
+            correlations
+                .entry(event.metadata().correlation_id())
+                .or_default()
+                .push(stored_event.clone());
+
+            if let Some(idempotency_key) = event.metadata().idempotency_key() {
+                idempotency_keys
+                    .entry((stream_id.clone(), idempotency_key.to_string()))
+                    .or_insert_with(|| stored_event.clone());
+            }
+
+            stream.push(stored_event);
+            typed_events.push(typed_event);
+        }
+
+        Ok(())
+    }
+
+    async fn read_event_type(&self, event_type: &str) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let inner = self.inner()?;
+
+        Ok(inner
+            .event_types
+            .get(event_type)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn read_correlation(
+        &self,
+        correlation_id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let inner = self.inner()?;
+
+        Ok(inner
+            .correlations
+            .get(&correlation_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn subscribe_event_type(
+        &self,
+        event_type: &str,
+        start: SubscriptionStart,
+    ) -> Result<EventSubscription, RepositoryError> {
+        let (sender, receiver) = unbounded_channel();
+
+        // the stored events are taken with the same lock as the subscription, so that
+        // no event is missed or received twice
+        let mut inner = self.inner()?;
+
+        let stored = inner
+            .event_types
+            .get(event_type)
+            .cloned()
+            .unwrap_or_default();
+        let stored = match start {
+            SubscriptionStart::Beginning => stored,
+            SubscriptionStart::End => Vec::new(),
+            SubscriptionStart::After(position) => {
+                stored.into_iter().skip(position as usize + 1).collect()
+            }
+        };
+
+        inner.subscribers.push((event_type.to_string(), sender));
+
+        let received = stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|event| (event, receiver))
+        });
+
+        let events = stream::iter(stored).chain(received).map(Ok);
+
+        Ok(Box::pin(events))
+    }
+}
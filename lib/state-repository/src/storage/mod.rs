@@ -0,0 +1,154 @@
+mod event_store_db;
+mod memory;
+mod sql;
+
+pub use memory::InMemoryStorage;
+pub(crate) use sql::is_unique_violation;
+pub use sql::SqlStorage;
+
+use crate::error::RepositoryError;
+use crate::metadata::{EventWithMetadata, Metadata};
+use crate::model_key::ModelKey;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+pub type EventSubscription = BoxStream<'static, Result<StoredEvent, RepositoryError>>;
+
+/// Where a subscription to an event type starts.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SubscriptionStart {
+    /// Every event of the type, the stored ones first.
+    Beginning,
+    /// Only the events appended from now on.
+    End,
+    /// The events after this position of the event type.
+    After(u64),
+}
+
+impl From<Option<u64>> for SubscriptionStart {
+    /// Start after a checkpoint, or from the beginning without one.
+    fn from(position: Option<u64>) -> Self {
+        match position {
+            None => SubscriptionStart::Beginning,
+            Some(position) => SubscriptionStart::After(position),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StoredEvent {
+    stream_id: String,
+    id: Uuid,
+    revision: u64,
+    position: Option<u64>,
+    event_type: String,
+    data: Vec<u8>,
+    custom_metadata: Vec<u8>,
+    created: Option<SystemTime>,
+}
+
+impl StoredEvent {
+    pub fn stream_id(&self) -> &str {
+        &self.stream_id
+    }
+    pub fn key(&self) -> ModelKey {
+        self.stream_id.clone().into()
+    }
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+    /// Position of the event among the events of its type, known when it has been read
+    /// or received by event type.
+    pub fn position(&self) -> Option<u64> {
+        self.position
+    }
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    pub fn custom_metadata(&self) -> &[u8] {
+        &self.custom_metadata
+    }
+    /// When the storage recorded the event, unknown for the events stored before it did.
+    pub fn created(&self) -> Option<SystemTime> {
+        self.created
+    }
+
+    pub fn as_json<T>(&self) -> serde_json::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_slice(&self.data)
+    }
+
+    pub fn metadata(&self) -> serde_json::Result<Metadata> {
+        let mut metadata: Metadata = serde_json::from_slice(&self.custom_metadata)?;
+        metadata.set_id(Some(self.id));
+        Ok(metadata)
+    }
+}
+
+/// Payloads which are not even json are kept as text.
+pub(crate) fn json_or_text(bytes: &[u8]) -> Value {
+    serde_json::from_slice(bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).into_owned()))
+}
+
+/// Backend where the streams of events are persisted.
+#[async_trait]
+pub trait EventStorage: Send + Sync {
+    /// Read a stream from `from_revision` included, or from its start.
+    async fn read_events(
+        &self,
+        key: &ModelKey,
+        from_revision: Option<u64>,
+    ) -> Result<Vec<StoredEvent>, RepositoryError>;
+
+    /// Read the last event of a stream, without reading the ones before.
+    async fn read_last_event(&self, key: &ModelKey)
+        -> Result<Option<StoredEvent>, RepositoryError>;
+
+    /// Read the first command or event of a stream whose metadata carries this
+    /// `$idempotencyKey`, the command given the key coming before the events it caused.
+    async fn read_idempotency_key(
+        &self,
+        key: &ModelKey,
+        idempotency_key: &str,
+    ) -> Result<Option<StoredEvent>, RepositoryError>;
+
+    /// Append to a stream expected to be at `expected_revision`, `None` meaning it must not exist.
+    /// Fail with `RepositoryError::Conflict` when the stream has moved in between.
+    async fn append_events(
+        &self,
+        key: &ModelKey,
+        expected_revision: Option<u64>,
+        events: Vec<EventWithMetadata>,
+    ) -> Result<(), RepositoryError>;
+
+    /// Read every event of a type, whatever its stream.
+    async fn read_event_type(&self, event_type: &str) -> Result<Vec<StoredEvent>, RepositoryError>;
+
+    /// Read every command and event whose metadata carries this `$correlationId`,
+    /// whatever their stream, in the order they were stored.
+    async fn read_correlation(
+        &self,
+        correlation_id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError>;
+
+    /// Receive the events of a type from `start`, whatever their stream, then the ones
+    /// appended later on.
+    async fn subscribe_event_type(
+        &self,
+        event_type: &str,
+        start: SubscriptionStart,
+    ) -> Result<EventSubscription, RepositoryError>;
+}
@@ -0,0 +1,501 @@
+use crate::error::RepositoryError;
+use crate::metadata::EventWithMetadata;
+use crate::model_key::ModelKey;
+use crate::storage::{EventStorage, EventSubscription, StoredEvent, SubscriptionStart};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use futures::stream;
+use sqlx::any::{AnyKind, AnyPool, AnyRow};
+use sqlx::migrate::Migrator;
+use sqlx::Row;
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{sleep, Duration, Instant};
+use uuid::Uuid;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const POLL_SIZE: i64 = 100;
+const GAP_TIMEOUT: Duration = Duration::from_secs(3);
+
+static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+static MYSQL_MIGRATOR: Migrator = sqlx::migrate!("./migrations/mysql");
+static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Event storage on a SQLite, MySQL / MariaDB or Postgres `events` table.
+///
+/// Subscriptions poll the table by event type, as a database cannot push new rows. They
+/// page through the positions, which appends running at once may commit out of order: a
+/// subscription does not move past a missing position until it is committed, or until it
+/// has been missing for `gap_timeout` as the append using it was rolled back.
+#[derive(Clone)]
+pub struct SqlStorage {
+    pool: AnyPool,
+    poll_interval: Duration,
+    gap_timeout: Duration,
+}
+
+impl SqlStorage {
+    pub fn new(pool: AnyPool) -> Self {
+        Self {
+            pool,
+            poll_interval: POLL_INTERVAL,
+            gap_timeout: GAP_TIMEOUT,
+        }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Longest time an append may take, after which a subscription skips the positions
+    /// it left missing.
+    pub fn with_gap_timeout(mut self, gap_timeout: Duration) -> Self {
+        self.gap_timeout = gap_timeout;
+        self
+    }
+
+    /// Create the `events` table, the migrations of the pool database being the ones
+    /// under `migrations/`. Other migrations already applied on the database are ignored.
+    pub async fn migrate(&self) -> Result<()> {
+        let events_migrator = match self.pool.any_kind() {
+            AnyKind::Sqlite => &SQLITE_MIGRATOR,
+            AnyKind::MySql => &MYSQL_MIGRATOR,
+            AnyKind::Postgres => &POSTGRES_MIGRATOR,
+        };
+
+        let migrator = Migrator {
+            migrations: events_migrator.migrations.clone(),
+            ignore_missing: true,
+            locking: events_migrator.locking,
+        };
+
+        migrator.run(&self.pool).await.context("migrate events")
+    }
+
+    pub fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+
+    /// Postgres numbers its placeholders, the other databases use `?`.
+    pub(crate) fn query(&self, query: &str) -> String {
+        if self.pool.any_kind() != AnyKind::Postgres {
+            return query.to_string();
+        }
+
+        let mut numbered = String::with_capacity(query.len());
+        let mut index = 0;
+        for c in query.chars() {
+            if c == '?' {
+                index += 1;
+                numbered.push_str(&format!("${index}"));
+            } else {
+                numbered.push(c);
+            }
+        }
+        numbered
+    }
+
+    async fn last_position(&self, event_type: &str) -> Result<i64, RepositoryError> {
+        let query = self.query("SELECT MAX(position) FROM events WHERE event_type = ?");
+
+        let position: Option<i64> = sqlx::query(&query)
+            .bind(event_type)
+            .fetch_one(&self.pool)
+            .await
+            .context("read last position")
+            .map_err(RepositoryError::StoreUnavailable)?
+            .try_get(0)
+            .context("decode last position")
+            .map_err(RepositoryError::Deserialization)?;
+
+        Ok(position.unwrap_or_default())
+    }
+
+    /// Position up to which every position after `committed` is committed or given up. A
+    /// missing position is waited for from `gap`, when it was first seen missing.
+    async fn committed_until(
+        &self,
+        committed: i64,
+        gap: &mut Option<(i64, Instant)>,
+    ) -> Result<i64, RepositoryError> {
+        let query =
+            self.query("SELECT position FROM events WHERE position > ? ORDER BY position LIMIT ?");
+
+        let rows = sqlx::query(&query)
+            .bind(committed)
+            .bind(POLL_SIZE)
+            .fetch_all(&self.pool)
+            .await
+            .context("read positions")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        let mut committed = committed;
+
+        for row in rows {
+            let position: i64 = row
+                .try_get("position")
+                .context("decode position")
+                .map_err(RepositoryError::Deserialization)?;
+
+            if position != committed + 1 {
+                let since = match *gap {
+                    Some((missing, since)) if missing == committed + 1 => since,
+                    _ => {
+                        *gap = Some((committed + 1, Instant::now()));
+                        break;
+                    }
+                };
+
+                if since.elapsed() < self.gap_timeout {
+                    break;
+                }
+            }
+
+            committed = position;
+        }
+
+        Ok(committed)
+    }
+
+    async fn read_event_type_after(
+        &self,
+        event_type: &str,
+        position: i64,
+        committed: i64,
+    ) -> Result<Vec<(i64, StoredEvent)>, RepositoryError> {
+        let query = self.query(
+            "SELECT position, stream_id, revision, event_id, event_type, data, metadata, created \
+             FROM events WHERE event_type = ? AND position > ? AND position <= ? \
+             ORDER BY position LIMIT ?",
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(event_type)
+            .bind(position)
+            .bind(committed)
+            .bind(POLL_SIZE)
+            .fetch_all(&self.pool)
+            .await
+            .context("read event type")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        rows.iter().map(typed_event).collect()
+    }
+}
+
+/// Subscription to an event type, after the last `position` delivered.
+struct Polling {
+    storage: SqlStorage,
+    event_type: String,
+    position: i64,
+    /// Every position up to this one is committed or given up.
+    committed: i64,
+    /// First position missing after `committed`, and when it was first seen.
+    gap: Option<(i64, Instant)>,
+    buffer: VecDeque<(i64, StoredEvent)>,
+}
+
+impl Polling {
+    /// Buffer the events committed since the last poll, waiting when there are none.
+    async fn poll(&mut self) -> Result<(), RepositoryError> {
+        let committed = self
+            .storage
+            .committed_until(self.committed, &mut self.gap)
+            .await?;
+
+        let events = self
+            .storage
+            .read_event_type_after(&self.event_type, self.position, committed)
+            .await?;
+
+        if events.is_empty() && committed == self.committed {
+            sleep(self.storage.poll_interval).await;
+        }
+
+        self.committed = committed;
+        self.buffer.extend(events);
+
+        Ok(())
+    }
+}
+
+fn stored_event(row: &AnyRow) -> Result<StoredEvent, RepositoryError> {
+    decode_stored_event(row)
+        .context("decode event row")
+        .map_err(RepositoryError::Deserialization)
+}
+
+/// Row read by event type, with its position.
+fn typed_event(row: &AnyRow) -> Result<(i64, StoredEvent), RepositoryError> {
+    let position: i64 = row
+        .try_get("position")
+        .context("decode position")
+        .map_err(RepositoryError::Deserialization)?;
+
+    let stored_event = StoredEvent {
+        position: Some(position as u64),
+        ..stored_event(row)?
+    };
+
+    Ok((position, stored_event))
+}
+
+fn decode_stored_event(row: &AnyRow) -> Result<StoredEvent> {
+    let revision: i64 = row.try_get("revision")?;
+    let id: String = row.try_get("event_id")?;
+    let data: String = row.try_get("data")?;
+    let metadata: String = row.try_get("metadata")?;
+    let created: Option<i64> = row.try_get("created")?;
+
+    Ok(StoredEvent {
+        stream_id: row.try_get("stream_id")?,
+        id: id.parse().context("decode event id")?,
+        revision: revision as u64,
+        position: None,
+        event_type: row.try_get("event_type")?,
+        data: data.into_bytes(),
+        custom_metadata: metadata.into_bytes(),
+        created: created.map(|created| UNIX_EPOCH + Duration::from_millis(created as u64)),
+    })
+}
+
+/// Record times are kept as milliseconds since the epoch.
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+pub(crate) fn is_unique_violation(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(e) => matches!(
+            e.code().as_deref(),
+            Some("23000") | Some("23505") | Some("1555") | Some("2067")
+        ),
+        _ => false,
+    }
+}
+
+#[async_trait]
+impl EventStorage for SqlStorage {
+    async fn read_events(
+        &self,
+        key: &ModelKey,
+        from_revision: Option<u64>,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let query = self.query(
+            "SELECT stream_id, revision, event_id, event_type, data, metadata, created \
+             FROM events WHERE stream_id = ? AND revision >= ? ORDER BY revision",
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(key.format())
+            .bind(from_revision.unwrap_or_default() as i64)
+            .fetch_all(&self.pool)
+            .await
+            .context("connect to event db")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        rows.iter().map(stored_event).collect()
+    }
+
+    async fn read_last_event(
+        &self,
+        key: &ModelKey,
+    ) -> Result<Option<StoredEvent>, RepositoryError> {
+        let query = self.query(
+            "SELECT stream_id, revision, event_id, event_type, data, metadata, created \
+             FROM events WHERE stream_id = ? ORDER BY revision DESC LIMIT 1",
+        );
+
+        let row = sqlx::query(&query)
+            .bind(key.format())
+            .fetch_optional(&self.pool)
+            .await
+            .context("connect to event db")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        row.as_ref().map(stored_event).transpose()
+    }
+
+    async fn read_idempotency_key(
+        &self,
+        key: &ModelKey,
+        idempotency_key: &str,
+    ) -> Result<Option<StoredEvent>, RepositoryError> {
+        let query = self.query(
+            "SELECT stream_id, revision, event_id, event_type, data, metadata, created \
+             FROM events WHERE stream_id = ? AND idempotency_key = ? ORDER BY revision LIMIT 1",
+        );
+
+        let row = sqlx::query(&query)
+            .bind(key.format())
+            .bind(idempotency_key)
+            .fetch_optional(&self.pool)
+            .await
+            .context("read idempotency key")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        row.as_ref().map(stored_event).transpose()
+    }
+
+    async fn append_events(
+        &self,
+        key: &ModelKey,
+        expected_revision: Option<u64>,
+        events: Vec<EventWithMetadata>,
+    ) -> Result<(), RepositoryError> {
+        let stream_id = key.format();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("begin append")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        let first_revision = expected_revision.map(|r| r + 1).unwrap_or_default();
+
+        let query = self.query(
+            "INSERT INTO events \
+             (stream_id, revision, event_id, event_type, data, metadata, correlation_id, created, \
+             idempotency_key) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        );
+
+        let mut revision = first_revision;
+
+        // written before anything is read, so that SQLite does not have to upgrade its lock
+        for event in events {
+            let inserted = sqlx::query(&query)
+                .bind(&stream_id)
+                .bind(revision as i64)
+                .bind(event.id().to_string())
+                .bind(event.event_type())
+                .bind(serde_json::to_string(event.data())?)
+                .bind(serde_json::to_string(event.metadata())?)
+                .bind(event.metadata().correlation_id().to_string())
+                .bind(now_millis())
+                .bind(event.metadata().idempotency_key())
+                .execute(&mut transaction)
+                .await;
+
+            match inserted {
+                Ok(_) => revision += 1,
+                Err(err) if is_unique_violation(&err) => {
+                    return Err(RepositoryError::Conflict(format!(
+                        "revision {revision} already exists"
+                    )));
+                }
+                Err(err) => {
+                    return Err(RepositoryError::StoreUnavailable(anyhow!(
+                        "error while appending : {:?}",
+                        err
+                    )))
+                }
+            }
+        }
+
+        // the events are rolled back with the transaction when the stream has moved
+        let query =
+            self.query("SELECT MAX(revision) FROM events WHERE stream_id = ? AND revision < ?");
+        let current_revision: Option<i64> = sqlx::query(&query)
+            .bind(&stream_id)
+            .bind(first_revision as i64)
+            .fetch_one(&mut transaction)
+            .await
+            .context("read current revision")
+            .map_err(RepositoryError::StoreUnavailable)?
+            .try_get(0)
+            .context("decode current revision")
+            .map_err(RepositoryError::Deserialization)?;
+
+        let current_revision = current_revision.map(|revision| revision as u64);
+        if current_revision != expected_revision {
+            return Err(RepositoryError::Conflict(format!(
+                "{current_revision:?} instead of {expected_revision:?}"
+            )));
+        }
+
+        transaction
+            .commit()
+            .await
+            .context("commit append")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        Ok(())
+    }
+
+    async fn read_event_type(&self, event_type: &str) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let query = self.query(
+            "SELECT position, stream_id, revision, event_id, event_type, data, metadata, created \
+             FROM events WHERE event_type = ? ORDER BY position",
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(event_type)
+            .fetch_all(&self.pool)
+            .await
+            .context("read event type")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        rows.iter().map(|row| Ok(typed_event(row)?.1)).collect()
+    }
+
+    async fn read_correlation(
+        &self,
+        correlation_id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let query = self.query(
+            "SELECT stream_id, revision, event_id, event_type, data, metadata, created \
+             FROM events WHERE correlation_id = ? ORDER BY position",
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(correlation_id.to_string())
+            .fetch_all(&self.pool)
+            .await
+            .context("read correlation")
+            .map_err(RepositoryError::StoreUnavailable)?;
+
+        rows.iter().map(stored_event).collect()
+    }
+
+    async fn subscribe_event_type(
+        &self,
+        event_type: &str,
+        start: SubscriptionStart,
+    ) -> Result<EventSubscription, RepositoryError> {
+        let position = match start {
+            SubscriptionStart::Beginning => 0,
+            SubscriptionStart::End => self.last_position(event_type).await?,
+            SubscriptionStart::After(position) => position as i64,
+        };
+
+        let state = Polling {
+            storage: self.clone(),
+            event_type: event_type.to_string(),
+            position,
+            committed: position,
+            gap: None,
+            buffer: VecDeque::new(),
+        };
+
+        let events = stream::unfold(state, |mut state| async move {
+            while state.buffer.is_empty() {
+                if let Err(err) = state.poll().await {
+                    return Some((Err(err), state));
+                }
+            }
+
+            let (position, event) = state.buffer.pop_front()?;
+            state.position = position;
+
+            Some((Ok(event), state))
+        });
+
+        Ok(Box::pin(events))
+    }
+}
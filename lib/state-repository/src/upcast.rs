@@ -0,0 +1,44 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type Upcast = dyn Fn(Value) -> Result<Value> + Send + Sync;
+
+/// Transformations of stored event payloads from a version to the next one, so that
+/// events written before a change of an `Event` type can still be played.
+#[derive(Clone, Default)]
+pub struct Upcasters {
+    upcasts: HashMap<(String, u32), Arc<Upcast>>,
+}
+
+impl Upcasters {
+    /// Register the transformation of the `event_type` payloads (like
+    /// `evt.account.Created`) from `from_version` to `from_version + 1`.
+    pub fn register<F>(
+        mut self,
+        event_type: impl Into<String>,
+        from_version: u32,
+        upcast: F,
+    ) -> Self
+    where
+        F: Fn(Value) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.upcasts
+            .insert((event_type.into(), from_version), Arc::new(upcast));
+        self
+    }
+
+    /// Upcast a payload of `version` to the last registered version of its event type.
+    pub fn upcast(&self, event_type: &str, version: u32, data: Value) -> Result<Value> {
+        let mut version = version;
+        let mut data = data;
+
+        while let Some(upcast) = self.upcasts.get(&(event_type.to_string(), version)) {
+            data = upcast(data).context(format!("upcast {event_type} from version {version}"))?;
+            version += 1;
+        }
+
+        Ok(data)
+    }
+}
@@ -1,63 +1,94 @@
-use crate::metadata::Metadata;
+use crate::consumer::{ConsumerHandle, Supervisor};
+use crate::error::RepositoryError;
 use crate::model_key::ModelKey;
-use crate::{StateRepository, EVENT_PREFIX};
+use crate::schedule::Scheduler;
+use crate::EVENT_PREFIX;
 use async_trait::async_trait;
-use eventstore::{StreamPosition, SubscribeToStreamOptions};
 use state::{EventName, State};
-use tokio::time::{sleep, Duration};
+use tokio::time::Duration;
+use uuid::Uuid;
+
+/// Id of a delayed action, given by the state in the event scheduling it so that later
+/// events can cancel or reschedule it.
+pub type ActionId = Uuid;
+
+/// What a delayed event asks of the scheduler.
+#[derive(Clone, Debug)]
+pub enum DelayedAction<C> {
+    /// Apply `command` after `delay`.
+    Schedule {
+        id: ActionId,
+        command: C,
+        delay: Duration,
+    },
+    /// Apply the command of a pending action `delay` from now instead.
+    Reschedule { id: ActionId, delay: Duration },
+    /// Forget a pending action.
+    Cancel { id: ActionId },
+}
 
 #[async_trait]
-pub trait DelayedState: State {
+pub trait DelayedState: State + 'static {
     fn event_to_delayed() -> Vec<EventName>;
 
-    fn resolve_command(event: Self::Event) -> (Self::Command, Duration);
+    fn resolve_action(event: Self::Event) -> DelayedAction<Self::Command>;
+
+    /// Take the actions of the delayed events after the checkpoints of the state, so that
+    /// the ones emitted while no processor was running are still taken. The commands are
+    /// applied by `scheduler` once due, and survive a restart as long as its store does.
+    ///
+    /// An event is acknowledged once its action is taken, scheduling an action id twice
+    /// being ignored. The delayed events being received by type, an action may be
+    /// cancelled or rescheduled before it is scheduled, the store of `scheduler` keeping
+    /// the cancel or the new delay for it. A cancelled action may also be applied
+    /// already: the state should refuse the command of an action it no longer waits for.
+    async fn process_delayed(
+        supervisor: &Supervisor,
+        scheduler: Scheduler,
+    ) -> Result<Vec<ConsumerHandle>, RepositoryError> {
+        scheduler.register::<Self>();
+
+        let consumer = format!("{}.delayed", Self::name_prefix());
+        let mut handles = Vec::new();
 
-    async fn process_delayed(repo: StateRepository) {
         for event_name in Self::event_to_delayed() {
-            let stream_name = format!(
-                "$et-{}.{}.{}",
-                EVENT_PREFIX,
-                Self::name_prefix(),
-                event_name
-            );
-            let repo = repo.clone();
-            tokio::spawn(async move {
-                let options = SubscribeToStreamOptions::default()
-                    .start_from(StreamPosition::End)
-                    .resolve_link_tos();
-
-                let mut stream = repo
-                    .event_db
-                    .clone()
-                    .subscribe_to_stream(stream_name, &options)
-                    .await;
-
-                loop {
-                    let event_json = stream.next().await.unwrap();
-                    let repo = repo.clone();
-
-                    if let Some(e) = event_json.event {
-                        tokio::spawn(async move {
-                            let mut metadata: Metadata =
-                                serde_json::from_slice(e.custom_metadata.as_ref()).unwrap();
-                            metadata.set_id(Some(e.id));
-
-                            let event = e.as_json::<Self::Event>().unwrap();
-                            let local_key: ModelKey = e.stream_id.clone().into();
-
-                            let repo = repo.clone();
-
-                            let (cmd, duration) = Self::resolve_command(event);
-
-                            sleep(duration).await;
-
-                            repo.add_command::<Self>(&local_key, cmd, Some(&metadata))
-                                .await
-                                .unwrap();
-                        });
+            let event_type = format!("{}.{}.{}", EVENT_PREFIX, Self::name_prefix(), event_name);
+            let scheduler = scheduler.clone();
+
+            let handle = supervisor
+                .consume(consumer.clone(), event_type, move |repo, e| {
+                    let scheduler = scheduler.clone();
+
+                    async move {
+                        let metadata = e.metadata()?;
+
+                        let event = repo.decode_event::<Self::Event>(&e)?;
+                        let local_key: ModelKey = e.key();
+
+                        match Self::resolve_action(event) {
+                            DelayedAction::Schedule { id, command, delay } => {
+                                scheduler
+                                    .schedule::<Self>(
+                                        id,
+                                        &local_key,
+                                        command,
+                                        delay,
+                                        Some(&metadata),
+                                    )
+                                    .await
+                            }
+                            DelayedAction::Reschedule { id, delay } => {
+                                scheduler.reschedule(id, delay).await.map(|_| ())
+                            }
+                            DelayedAction::Cancel { id } => scheduler.cancel(id).await.map(|_| ()),
+                        }
                     }
-                }
-            });
+                })
+                .await?;
+
+            handles.push(handle);
         }
+
+        Ok(handles)
     }
 }
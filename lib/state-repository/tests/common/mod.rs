@@ -0,0 +1,237 @@
+// each test binary uses a part of these helpers
+#![allow(dead_code)]
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::stream;
+use state_repository::error::RepositoryError;
+use state_repository::metadata::EventWithMetadata;
+use state_repository::model_key::ModelKey;
+use state_repository::schedule::{InMemoryScheduleStore, ScheduleStore, ScheduledCommand};
+use state_repository::storage::{
+    EventStorage, EventSubscription, InMemoryStorage, StoredEvent, SubscriptionStart,
+};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::time::{sleep, timeout, Duration};
+use uuid::Uuid;
+
+/// Wait until `done` holds, polling it, failing after 5 seconds.
+pub async fn eventually<F, Fut>(what: &str, mut done: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    timeout(Duration::from_secs(5), async {
+        while !done().await {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("timed out waiting for {what}"));
+}
+
+/// Appends refused by a `FaultyStorage`.
+#[derive(Clone, Default)]
+enum AppendFault {
+    #[default]
+    None,
+    /// Every stream moved since it was read.
+    Conflict,
+    /// The streams of this name are locked.
+    Locked(String),
+}
+
+#[derive(Default)]
+struct Faults {
+    failing_subscriptions: AtomicUsize,
+    append: Mutex<AppendFault>,
+    read_delay: Mutex<Duration>,
+    reading: AtomicUsize,
+    max_reading: AtomicUsize,
+}
+
+/// Storage in memory failing or slowing down the calls it is told to, the faults being
+/// shared between its clones.
+#[derive(Clone, Default)]
+pub struct FaultyStorage {
+    storage: InMemoryStorage,
+    faults: Arc<Faults>,
+}
+
+impl FaultyStorage {
+    /// Fail the next `nb` subscriptions at once, as a store going down.
+    pub fn fail_subscriptions(&self, nb: usize) {
+        self.faults
+            .failing_subscriptions
+            .store(nb, Ordering::SeqCst);
+    }
+
+    /// Refuse every append with a conflict.
+    pub fn conflict_appends(&self) {
+        *self.faults.append.lock().unwrap() = AppendFault::Conflict;
+    }
+
+    /// Refuse the appends to the streams of `stream_name` until unlocked.
+    pub fn lock(&self, stream_name: &str) {
+        *self.faults.append.lock().unwrap() = AppendFault::Locked(stream_name.to_string());
+    }
+
+    pub fn unlock(&self) {
+        *self.faults.append.lock().unwrap() = AppendFault::None;
+    }
+
+    /// Make each read of a stream last `delay`.
+    pub fn slow_reads(&self, delay: Duration) {
+        *self.faults.read_delay.lock().unwrap() = delay;
+    }
+
+    /// Most reads of a stream there were at once since the last reset.
+    pub fn max_reading(&self) -> usize {
+        self.faults.max_reading.load(Ordering::SeqCst)
+    }
+
+    pub fn reset_max_reading(&self) {
+        self.faults.max_reading.store(0, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl EventStorage for FaultyStorage {
+    async fn read_events(
+        &self,
+        key: &ModelKey,
+        from_revision: Option<u64>,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let reading = self.faults.reading.fetch_add(1, Ordering::SeqCst) + 1;
+        self.faults.max_reading.fetch_max(reading, Ordering::SeqCst);
+
+        let delay = *self.faults.read_delay.lock().unwrap();
+        sleep(delay).await;
+        let events = self.storage.read_events(key, from_revision).await;
+
+        self.faults.reading.fetch_sub(1, Ordering::SeqCst);
+        events
+    }
+
+    async fn read_last_event(
+        &self,
+        key: &ModelKey,
+    ) -> Result<Option<StoredEvent>, RepositoryError> {
+        self.storage.read_last_event(key).await
+    }
+
+    async fn read_idempotency_key(
+        &self,
+        key: &ModelKey,
+        idempotency_key: &str,
+    ) -> Result<Option<StoredEvent>, RepositoryError> {
+        self.storage
+            .read_idempotency_key(key, idempotency_key)
+            .await
+    }
+
+    async fn append_events(
+        &self,
+        key: &ModelKey,
+        expected_revision: Option<u64>,
+        events: Vec<EventWithMetadata>,
+    ) -> Result<(), RepositoryError> {
+        let fault = self.faults.append.lock().unwrap().clone();
+
+        match fault {
+            AppendFault::Conflict => {
+                return Err(RepositoryError::Conflict("always moved".to_string()));
+            }
+            AppendFault::Locked(stream_name) if key.stream_name() == stream_name => {
+                return Err(RepositoryError::Processing(anyhow!("{stream_name} locked")));
+            }
+            _ => {}
+        }
+
+        self.storage
+            .append_events(key, expected_revision, events)
+            .await
+    }
+
+    async fn read_event_type(&self, event_type: &str) -> Result<Vec<StoredEvent>, RepositoryError> {
+        self.storage.read_event_type(event_type).await
+    }
+
+    async fn read_correlation(
+        &self,
+        correlation_id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        self.storage.read_correlation(correlation_id).await
+    }
+
+    async fn subscribe_event_type(
+        &self,
+        event_type: &str,
+        start: SubscriptionStart,
+    ) -> Result<EventSubscription, RepositoryError> {
+        let failing = self.faults.failing_subscriptions.fetch_update(
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+            |nb| nb.checked_sub(1),
+        );
+
+        if failing.is_ok() {
+            let down = RepositoryError::StoreUnavailable(anyhow!("down"));
+            return Ok(Box::pin(stream::once(async { Err(down) })));
+        }
+
+        self.storage.subscribe_event_type(event_type, start).await
+    }
+}
+
+/// Schedule store in memory failing the calls it is told to, the faults being shared
+/// between its clones.
+#[derive(Clone, Default)]
+pub struct FaultyScheduleStore {
+    store: InMemoryScheduleStore,
+    failing_dues: Arc<AtomicUsize>,
+    add_delay: Arc<Mutex<Duration>>,
+}
+
+impl FaultyScheduleStore {
+    /// Make each add of a command last `delay`.
+    pub fn slow_adds(&self, delay: Duration) {
+        *self.add_delay.lock().unwrap() = delay;
+    }
+
+    /// Fail the next `nb` looks for the due commands.
+    pub fn fail_dues(&self, nb: usize) {
+        self.failing_dues.store(nb, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for FaultyScheduleStore {
+    async fn add(&self, command: ScheduledCommand) -> anyhow::Result<()> {
+        let delay = *self.add_delay.lock().unwrap();
+        sleep(delay).await;
+        self.store.add(command).await
+    }
+
+    async fn due(&self, now: SystemTime) -> anyhow::Result<Vec<ScheduledCommand>> {
+        let failing = self
+            .failing_dues
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |nb| nb.checked_sub(1));
+
+        if failing.is_ok() {
+            return Err(anyhow!("schedule store unavailable"));
+        }
+        self.store.due(now).await
+    }
+
+    async fn reschedule(&self, id: Uuid, due: SystemTime) -> anyhow::Result<bool> {
+        self.store.reschedule(id, due).await
+    }
+
+    async fn remove(&self, id: Uuid) -> anyhow::Result<bool> {
+        self.store.remove(id).await
+    }
+}
@@ -1,7 +1,7 @@
 use crate::concurrent::ConcurrentEvent::TimeTaken;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use state::{Command, Event, State};
+use state::{Command, CommandContext, Event, State};
 use std::{thread, time};
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -38,6 +38,7 @@ pub struct ConcurrentState {
 impl State for ConcurrentState {
     type Event = ConcurrentEvent;
     type Command = ConcurrentCommand;
+    type Error = anyhow::Error;
 
     fn name_prefix() -> &'static str {
         "concurrent"
@@ -51,7 +52,11 @@ impl State for ConcurrentState {
         }
     }
 
-    fn try_command(&self, command: Self::Command) -> Result<Vec<Self::Event>> {
+    fn try_command(
+        &self,
+        command: Self::Command,
+        _context: &CommandContext,
+    ) -> Result<Vec<Self::Event>> {
         match command {
             ConcurrentCommand::TakeTime(time, name) => {
                 let wait = time::Duration::from_millis((100 * time) as u64);
@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use state::{Command, CommandContext, CommandName, Event, EventName, State, StateName};
+use state_repository::waiter::{ActionId, DelayedAction, DelayedState};
+use tokio::time::Duration;
+
+const CONSTRUCTION_STATE_PREFIX: &str = "test-construction";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum ConstructionCommand {
+    Start(Duration),
+    Cancel,
+    SpeedUp(Duration),
+    Finish(ActionId),
+}
+
+impl Command for ConstructionCommand {
+    fn command_name(&self) -> CommandName {
+        use ConstructionCommand::*;
+        match &self {
+            Start(_) => "Start",
+            Cancel => "Cancel",
+            SpeedUp(_) => "SpeedUp",
+            Finish(_) => "Finish",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum ConstructionEvent {
+    Started(ActionId, Duration),
+    Cancelled(ActionId),
+    Rescheduled(ActionId, Duration),
+    Finished,
+}
+
+impl Event for ConstructionEvent {
+    fn event_name(&self) -> EventName {
+        use ConstructionEvent::*;
+        match &self {
+            Started(_, _) => "started",
+            Cancelled(_) => "cancelled",
+            Rescheduled(_, _) => "rescheduled",
+            Finished => "finished",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ConstructionState {
+    pub pending: Option<ActionId>,
+    pub built: bool,
+    pub cancelled: u32,
+}
+
+impl State for ConstructionState {
+    type Event = ConstructionEvent;
+    type Command = ConstructionCommand;
+    type Error = anyhow::Error;
+
+    fn name_prefix() -> StateName {
+        CONSTRUCTION_STATE_PREFIX
+    }
+
+    fn play_event(&mut self, event: &Self::Event) {
+        use ConstructionEvent::*;
+        match event {
+            Started(id, _) => self.pending = Some(*id),
+            Cancelled(_) => {
+                self.pending = None;
+                self.cancelled += 1;
+            }
+            Rescheduled(_, _) => {}
+            Finished => {
+                self.pending = None;
+                self.built = true;
+            }
+        }
+    }
+
+    fn try_command(
+        &self,
+        command: Self::Command,
+        context: &CommandContext,
+    ) -> Result<Vec<Self::Event>> {
+        use ConstructionCommand::*;
+        use ConstructionEvent::*;
+        match (command, self.pending) {
+            (Start(_), Some(_)) => Err(anyhow!("already started")),
+            (Start(delay), None) => Ok(vec![Started(context.new_id(), delay)]),
+            (Cancel, Some(id)) => Ok(vec![Cancelled(id)]),
+            (SpeedUp(delay), Some(id)) => Ok(vec![Rescheduled(id, delay)]),
+            (Finish(id), Some(pending)) if id == pending => Ok(vec![Finished]),
+            (Finish(id), _) => Err(anyhow!("action {id} is not pending")),
+            (Cancel, None) | (SpeedUp(_), None) => Err(anyhow!("nothing started")),
+        }
+    }
+}
+
+impl DelayedState for ConstructionState {
+    fn event_to_delayed() -> Vec<EventName> {
+        vec!["started", "cancelled", "rescheduled"]
+    }
+
+    fn resolve_action(event: Self::Event) -> DelayedAction<Self::Command> {
+        use ConstructionEvent::*;
+        match event {
+            Started(id, delay) => DelayedAction::Schedule {
+                id,
+                command: ConstructionCommand::Finish(id),
+                delay,
+            },
+            Cancelled(id) => DelayedAction::Cancel { id },
+            Rescheduled(id, delay) => DelayedAction::Reschedule { id, delay },
+            Finished => unimplemented!(),
+        }
+    }
+}
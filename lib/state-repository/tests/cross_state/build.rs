@@ -1,12 +1,14 @@
 use crate::cross_state::build_api::{PaymentQuestion, PublicBuild};
-use anyhow::Result;
-use eventstore::RecordedEvent;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use state::{Command, CommandName, Event, EventName, State};
+use state::{Command, CommandContext, Event, State};
 use state_repository::cross_state::{CrossData, CrossDataProcessor, CrossStateAnswer};
+use state_repository::error::RepositoryError;
 use state_repository::model_key::ModelKey;
+use state_repository::storage::StoredEvent;
+use state_repository::StateRepository;
 
-pub const BUILD_STATE_NAME: &'static str = "test-tower";
+pub const BUILD_STATE_NAME: &str = "test-tower";
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct BuildingCreate {
@@ -14,58 +16,39 @@ pub struct BuildingCreate {
     pub bank: ModelKey,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Command)]
 pub enum BuildCommand {
     Create(BuildingCreate),
     Pay(u32),
+    Refuse(String),
+    TimeOut,
 }
 
-impl Command for BuildCommand {
-    fn command_name(&self) -> CommandName {
-        use BuildCommand::*;
-        match &self {
-            Create(_) => "Create",
-            Pay(_) => "Pay",
-        }
-    }
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Event)]
 #[serde(tag = "type")]
 pub enum BuildEvent {
+    #[event(name = "created")]
     Created(BuildingCreate),
+    #[event(name = "build")]
     Built,
+    #[event(name = "failed")]
+    Failed { reason: String },
+    #[event(forward)]
     Public(PaymentQuestion),
 }
 
-impl Event for BuildEvent {
-    fn event_name(&self) -> EventName {
-        use BuildEvent::*;
-        match &self {
-            Created(_) => "created",
-            Built => "build",
-            Public(p) => p.event_name(),
-        }
-    }
-
-    fn is_state_specific(&self) -> bool {
-        match &self {
-            BuildEvent::Public(_) => false,
-            _ => true,
-        }
-    }
-}
-
 #[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
 pub struct BuildState {
     pub cost: u32,
     pub built: bool,
     pub bank: Option<ModelKey>,
+    pub failure: Option<String>,
 }
 
 impl State for BuildState {
     type Event = BuildEvent;
     type Command = BuildCommand;
+    type Error = anyhow::Error;
 
     fn name_prefix() -> &'static str {
         BUILD_STATE_NAME
@@ -80,11 +63,16 @@ impl State for BuildState {
                 self.bank = Some(create.bank.clone());
             }
             Built => self.built = true,
+            Failed { reason } => self.failure = Some(reason.clone()),
             Public(_) => {}
         }
     }
 
-    fn try_command(&self, command: Self::Command) -> Result<Vec<Self::Event>> {
+    fn try_command(
+        &self,
+        command: Self::Command,
+        _context: &CommandContext,
+    ) -> Result<Vec<Self::Event>> {
         use BuildCommand::*;
         use BuildEvent::*;
         match command {
@@ -95,24 +83,34 @@ impl State for BuildState {
                     bank: c.bank.clone(),
                 }),
             ]),
+            _ if self.built => Err(anyhow!("already built")),
+            _ if self.failure.is_some() => Err(anyhow!("building failed")),
             Pay(c) => {
                 if c >= self.cost {
                     Ok(vec![Built])
                 } else {
-                    todo!("in real case it should be handle wink")
+                    Err(anyhow!("{} gold missing in payment", self.cost - c))
                 }
             }
+            Refuse(reason) => Ok(vec![Failed { reason }]),
+            TimeOut => Ok(vec![Failed {
+                reason: "payment not answered".to_string(),
+            }]),
         }
     }
 
-    fn state_cache_interval() -> Option<u64> {
+    fn snapshot_interval() -> Option<u64> {
         None
     }
 }
 
 impl CrossDataProcessor for BuildState {
-    fn resolve(e: RecordedEvent, _local_key: ModelKey) -> (Self::Command, ModelKey) {
-        Self::resolve_helper(e)
+    fn resolve(
+        repo: &StateRepository,
+        e: StoredEvent,
+        _local_key: ModelKey,
+    ) -> Result<(Self::Command, ModelKey), RepositoryError> {
+        Self::resolve_helper(repo, e)
     }
 }
 
@@ -120,4 +118,12 @@ impl CrossStateAnswer<PublicBuild> for BuildState {
     fn resolve_answer(event: <PublicBuild as CrossData>::Answer) -> Self::Command {
         BuildCommand::Pay(event.amount)
     }
+
+    fn resolve_refusal(event: <PublicBuild as CrossData>::Refusal) -> Self::Command {
+        BuildCommand::Refuse(format!("{} gold missing", event.missing))
+    }
+
+    fn resolve_timeout(_question: <PublicBuild as CrossData>::Question) -> Self::Command {
+        BuildCommand::TimeOut
+    }
 }
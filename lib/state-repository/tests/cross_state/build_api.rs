@@ -2,41 +2,47 @@ use serde::{Deserialize, Serialize};
 use state::{CommandName, Event, EventName};
 use state_repository::cross_state::{CrossData, HasTarget};
 use state_repository::model_key::ModelKey;
+use tokio::time::Duration;
 
-pub const PAYMENT_ASKED: &'static str = "payment_asked";
-pub const PAYMENT_DONE: &'static str = "payment_done";
+pub const PAYMENT_ASKED: &str = "payment_asked";
+pub const PAYMENT_DONE: &str = "payment_done";
+pub const PAYMENT_REFUSED: &str = "payment_refused";
+pub const ANSWER_DEADLINE: Duration = Duration::from_secs(60);
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Event)]
+#[event(name = PAYMENT_ASKED, public)]
 pub struct PaymentQuestion {
     pub amount: u32,
     pub bank: ModelKey,
 }
 
-impl Event for PaymentQuestion {
-    fn event_name(&self) -> EventName {
-        PAYMENT_ASKED
-    }
-}
-
 impl HasTarget for PaymentQuestion {
     fn get_target(&self) -> ModelKey {
         self.bank.clone()
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Event)]
+#[event(name = PAYMENT_DONE, public)]
 pub struct PaymentResponse {
     pub amount: u32,
     pub response: ModelKey,
 }
 
-impl Event for PaymentResponse {
-    fn event_name(&self) -> EventName {
-        PAYMENT_DONE
+impl HasTarget for PaymentResponse {
+    fn get_target(&self) -> ModelKey {
+        self.response.clone()
     }
 }
 
-impl HasTarget for PaymentResponse {
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Event)]
+#[event(name = PAYMENT_REFUSED, public)]
+pub struct PaymentRefusal {
+    pub missing: u32,
+    pub response: ModelKey,
+}
+
+impl HasTarget for PaymentRefusal {
     fn get_target(&self) -> ModelKey {
         self.response.clone()
     }
@@ -47,6 +53,7 @@ pub struct PublicBuild {}
 impl CrossData for PublicBuild {
     type Question = PaymentQuestion;
     type Answer = PaymentResponse;
+    type Refusal = PaymentRefusal;
 
     fn question_names() -> Vec<EventName> {
         vec![PAYMENT_ASKED]
@@ -55,4 +62,12 @@ impl CrossData for PublicBuild {
     fn answer_names() -> Vec<CommandName> {
         vec![PAYMENT_DONE]
     }
+
+    fn refusal_names() -> Vec<EventName> {
+        vec![PAYMENT_REFUSED]
+    }
+
+    fn answer_deadline() -> Option<Duration> {
+        Some(ANSWER_DEADLINE)
+    }
 }
@@ -1,57 +1,36 @@
-use crate::cross_state::build_api::{PaymentResponse, PublicBuild};
+use crate::cross_state::build_api::{PaymentRefusal, PaymentResponse, PublicBuild};
 use anyhow::Result;
-use eventstore::RecordedEvent;
 use serde::{Deserialize, Serialize};
-use state::{Command, Event, State};
+use state::{Command, CommandContext, Event, State};
 use state_repository::cross_state::{CrossData, CrossDataProcessor, CrossStateQuestion};
+use state_repository::error::RepositoryError;
 use state_repository::model_key::ModelKey;
+use state_repository::storage::StoredEvent;
+use state_repository::StateRepository;
 use std::fmt::Debug;
 
-pub const PAID: &'static str = "paid";
-pub const GOLD_STATE_NAME: &'static str = "test-gold";
+pub const PAID: &str = "paid";
+pub const GOLD_STATE_NAME: &str = "test-gold";
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Command)]
 pub enum GoldCommand {
     Pay(u32, ModelKey),
 }
 
-impl Command for GoldCommand {
-    fn command_name(&self) -> &'static str {
-        use GoldCommand::*;
-        match &self {
-            Pay(_, _) => "Pay",
-        }
-    }
-}
-
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Cost {
     amount: u32,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Event)]
 #[serde(tag = "type")]
 pub enum GoldEvent {
+    #[event(name = PAID)]
     Paid(Cost),
+    #[event(forward)]
     Public(PaymentResponse),
-}
-
-impl Event for GoldEvent {
-    fn event_name(&self) -> &'static str {
-        use GoldEvent::*;
-
-        match &self {
-            Paid(_) => PAID,
-            Public(p) => p.event_name(),
-        }
-    }
-
-    fn is_state_specific(&self) -> bool {
-        match &self {
-            GoldEvent::Public(_) => false,
-            _ => true,
-        }
-    }
+    #[event(forward)]
+    PublicRefusal(PaymentRefusal),
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -68,6 +47,7 @@ impl Default for GoldState {
 impl State for GoldState {
     type Event = GoldEvent;
     type Command = GoldCommand;
+    type Error = anyhow::Error;
 
     fn name_prefix() -> &'static str {
         GOLD_STATE_NAME
@@ -76,12 +56,22 @@ impl State for GoldState {
     fn play_event(&mut self, event: &Self::Event) {
         match event {
             GoldEvent::Paid(c) => self.nb -= c.amount,
-            GoldEvent::Public(_) => {}
+            GoldEvent::Public(_) | GoldEvent::PublicRefusal(_) => {}
         }
     }
 
-    fn try_command(&self, command: Self::Command) -> Result<Vec<Self::Event>> {
+    fn try_command(
+        &self,
+        command: Self::Command,
+        _context: &CommandContext,
+    ) -> Result<Vec<Self::Event>> {
         match command {
+            GoldCommand::Pay(n, k) if n > self.nb => {
+                Ok(vec![GoldEvent::PublicRefusal(PaymentRefusal {
+                    missing: n - self.nb,
+                    response: k,
+                })])
+            }
             GoldCommand::Pay(n, k) => Ok(vec![
                 GoldEvent::Paid(Cost { amount: n }),
                 GoldEvent::Public(PaymentResponse {
@@ -92,14 +82,18 @@ impl State for GoldState {
         }
     }
 
-    fn state_cache_interval() -> Option<u64> {
+    fn snapshot_interval() -> Option<u64> {
         None
     }
 }
 
 impl CrossDataProcessor for GoldState {
-    fn resolve(e: RecordedEvent, local_key: ModelKey) -> (Self::Command, ModelKey) {
-        Self::resolve_helper(e, local_key)
+    fn resolve(
+        repo: &StateRepository,
+        e: StoredEvent,
+        local_key: ModelKey,
+    ) -> Result<(Self::Command, ModelKey), RepositoryError> {
+        Self::resolve_helper(repo, e, local_key)
     }
 }
 
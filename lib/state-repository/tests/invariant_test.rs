@@ -0,0 +1,138 @@
+use crate::cross_state::gold::{GoldCommand, GoldState};
+use crate::simple::{SimpleCommand, SimpleState};
+use crate::wait::{WaitCommand, WaitState};
+use proptest::prelude::*;
+use serde::{Deserialize, Serialize};
+use state::invariant::InvariantCheck;
+use state::{Command, CommandContext, Event, State};
+use state_repository::model_key::ModelKey;
+
+mod cross_state {
+    pub mod build_api;
+    pub mod gold;
+}
+mod simple;
+mod wait;
+
+/// Gold paying whatever it is asked, as `GoldState` did before refusing.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+struct UncheckedGoldState {
+    nb: u32,
+}
+
+impl Default for UncheckedGoldState {
+    fn default() -> Self {
+        UncheckedGoldState { nb: 1000 }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Command)]
+enum UncheckedGoldCommand {
+    Pay(u32),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Event)]
+enum UncheckedGoldEvent {
+    #[event(name = "paid")]
+    Paid(u32),
+}
+
+impl State for UncheckedGoldState {
+    type Event = UncheckedGoldEvent;
+    type Command = UncheckedGoldCommand;
+    type Error = anyhow::Error;
+
+    fn name_prefix() -> &'static str {
+        "test-unchecked-gold"
+    }
+
+    fn play_event(&mut self, event: &Self::Event) {
+        match event {
+            UncheckedGoldEvent::Paid(n) => self.nb -= n,
+        }
+    }
+
+    fn try_command(
+        &self,
+        command: Self::Command,
+        _context: &CommandContext,
+    ) -> anyhow::Result<Vec<Self::Event>> {
+        match command {
+            UncheckedGoldCommand::Pay(n) => Ok(vec![UncheckedGoldEvent::Paid(n)]),
+        }
+    }
+
+    fn snapshot_interval() -> Option<u64> {
+        None
+    }
+}
+
+#[test]
+fn simple_case() {
+    InvariantCheck::<SimpleState>::default()
+        .transition(
+            "moved by the command",
+            |before, command, after| match *command {
+                SimpleCommand::Add(n) => before.nb.checked_add(n) == Some(after.nb),
+                SimpleCommand::Remove(n) => before.nb.checked_sub(n) == Some(after.nb),
+                SimpleCommand::Set(n) => after.nb == n,
+            },
+        )
+        .check(prop_oneof![
+            any::<u32>().prop_map(SimpleCommand::Add),
+            any::<u32>().prop_map(SimpleCommand::Remove),
+            any::<u32>().prop_map(SimpleCommand::Set),
+        ]);
+}
+
+#[test]
+fn wait_case() {
+    // a growth used to remove more than the state had
+    InvariantCheck::<WaitState>::default()
+        .transition(
+            "growth removes what it grows",
+            |before, command, after| match *command {
+                WaitCommand::Growth(n) => before.nb.checked_sub(n) == Some(after.nb),
+                WaitCommand::Add(n) | WaitCommand::GrowEnd(n) => {
+                    before.nb.checked_add(n) == Some(after.nb)
+                }
+            },
+        )
+        .check(prop_oneof![
+            (0..100u32).prop_map(WaitCommand::Add),
+            (0..100u32).prop_map(WaitCommand::Growth),
+            (0..100u32).prop_map(WaitCommand::GrowEnd),
+        ]);
+}
+
+#[test]
+fn gold_case() {
+    let key = ModelKey::new("invariant_test".to_string(), "bank".to_string());
+
+    // a payment above the gold left used to be paid anyway
+    InvariantCheck::<GoldState>::default()
+        .invariant("never above the first gold", |state| state.nb <= 1000)
+        .transition("pays or refuses", |before, command, after| match command {
+            GoldCommand::Pay(n, _) => {
+                before.nb.checked_sub(*n) == Some(after.nb) || before.nb == after.nb
+            }
+        })
+        .check((0..600u32).prop_map(move |n| GoldCommand::Pay(n, key.clone())));
+}
+
+#[test]
+#[should_panic(expected = "Pay(")]
+fn unchecked_gold_case() {
+    InvariantCheck::<UncheckedGoldState>::default()
+        .invariant("never above the first gold", |state| state.nb <= 1000)
+        .check((0..600u32).prop_map(UncheckedGoldCommand::Pay));
+}
+
+#[test]
+#[should_panic(expected = "invariant below ten broken by command")]
+fn broken_case() {
+    InvariantCheck::<SimpleState>::default()
+        .invariant("below ten", |state| state.nb < 10)
+        .max_commands(5)
+        .check((0..100u32).prop_map(SimpleCommand::Add));
+}
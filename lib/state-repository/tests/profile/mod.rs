@@ -0,0 +1,91 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use state::{Command, CommandContext, Event, State};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub enum ProfileCommand {
+    Rename(String, String),
+}
+
+impl Command for ProfileCommand {
+    fn command_name(&self) -> &'static str {
+        match &self {
+            ProfileCommand::Rename(_, _) => "Rename",
+        }
+    }
+}
+
+/// First version of the events, the name being a single field.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum ProfileEventV1 {
+    Renamed(String),
+}
+
+impl Event for ProfileEventV1 {
+    fn event_name(&self) -> &'static str {
+        match &self {
+            ProfileEventV1::Renamed(_) => "renamed",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum ProfileEvent {
+    Renamed {
+        first_name: String,
+        last_name: String,
+    },
+}
+
+impl Event for ProfileEvent {
+    fn event_name(&self) -> &'static str {
+        match &self {
+            ProfileEvent::Renamed { .. } => "renamed",
+        }
+    }
+
+    fn event_version(&self) -> u32 {
+        2
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ProfileState {
+    pub first_name: String,
+    pub last_name: String,
+}
+
+impl State for ProfileState {
+    type Event = ProfileEvent;
+    type Command = ProfileCommand;
+    type Error = anyhow::Error;
+
+    fn name_prefix() -> &'static str {
+        "test-profile"
+    }
+
+    fn play_event(&mut self, event: &Self::Event) {
+        match event {
+            ProfileEvent::Renamed {
+                first_name,
+                last_name,
+            } => {
+                self.first_name = first_name.clone();
+                self.last_name = last_name.clone();
+            }
+        }
+    }
+
+    fn try_command(
+        &self,
+        command: Self::Command,
+        _context: &CommandContext,
+    ) -> Result<Vec<Self::Event>> {
+        match command {
+            ProfileCommand::Rename(first_name, last_name) => Ok(vec![ProfileEvent::Renamed {
+                first_name,
+                last_name,
+            }]),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::simple::{SimpleCommand, SimpleEvent, SimpleState};
+use async_trait::async_trait;
+use sqlx::any::AnyPoolOptions;
+use state_repository::checkpoint::{CheckpointStore, InMemoryCheckpointStore, SqlCheckpointStore};
+use state_repository::consumer::{ProcessorState, Supervisor};
+use state_repository::model_key::ModelKey;
+use state_repository::projection::{Projection, ProjectionRunner};
+use state_repository::retry::RetryPolicy;
+use state_repository::snapshot::InMemorySnapshotStore;
+use state_repository::storage::{InMemoryStorage, SqlStorage, StoredEvent};
+use state_repository::StateRepository;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+mod simple;
+
+const ADDED: &str = "evt.test-simple.added";
+
+/// Sum of every number added, whatever the stream.
+#[derive(Default)]
+struct TotalAdded {
+    total: AtomicU32,
+    /// Fail the next event applied, once.
+    failing: AtomicBool,
+}
+
+#[async_trait]
+impl Projection for TotalAdded {
+    fn name(&self) -> &str {
+        "total-added"
+    }
+
+    fn event_types(&self) -> Vec<String> {
+        vec![ADDED.to_string()]
+    }
+
+    async fn apply(&self, repo: &StateRepository, event: &StoredEvent) -> anyhow::Result<()> {
+        if self.failing.swap(false, Ordering::SeqCst) {
+            anyhow::bail!("read model unavailable");
+        }
+        if let SimpleEvent::Added(n) = repo.decode_event::<SimpleEvent>(event)? {
+            self.total.fetch_add(n, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    async fn reset(&self) -> anyhow::Result<()> {
+        self.total.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl TotalAdded {
+    async fn wait_for(&self, total: u32) {
+        for _ in 0..100 {
+            if self.total.load(Ordering::SeqCst) == total {
+                return;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(self.total.load(Ordering::SeqCst), total);
+    }
+}
+
+async fn add(repo: &StateRepository, n: u32) {
+    let key = ModelKey::new("projection_test".to_string(), Uuid::new_v4().to_string());
+    repo.add_command::<SimpleState>(&key, SimpleCommand::Add(n), None)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn catch_up_case() {
+    let repo = StateRepository::new(InMemoryStorage::default(), InMemorySnapshotStore::default());
+    let checkpoints = InMemoryCheckpointStore::default();
+    let runner = ProjectionRunner::new(repo.clone(), checkpoints.clone());
+
+    add(&repo, 1).await;
+    add(&repo, 2).await;
+
+    let projection = Arc::new(TotalAdded::default());
+    let handle = runner.start(projection.clone()).await.unwrap();
+
+    add(&repo, 4).await;
+    projection.wait_for(7).await;
+    handle.abort();
+
+    assert_eq!(
+        checkpoints.load("total-added", ADDED).await.unwrap(),
+        Some(2)
+    );
+
+    // a restarted projection only applies the events after its checkpoint
+    add(&repo, 8).await;
+
+    let restarted = Arc::new(TotalAdded::default());
+    let handle = runner.start(restarted.clone()).await.unwrap();
+    restarted.wait_for(8).await;
+    handle.abort();
+
+    let handle = runner.rebuild(restarted.clone()).await.unwrap();
+    restarted.wait_for(15).await;
+    handle.abort();
+}
+
+#[tokio::test]
+async fn supervised_case() {
+    let repo = StateRepository::new(InMemoryStorage::default(), InMemorySnapshotStore::default());
+    let runner = ProjectionRunner::new(repo.clone(), InMemoryCheckpointStore::default());
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default())
+        .with_restart_policy(RetryPolicy::default().initial_backoff(Duration::from_millis(10)));
+
+    add(&repo, 1).await;
+
+    let projection = Arc::new(TotalAdded::default());
+    projection.failing.store(true, Ordering::SeqCst);
+    let handles = runner.supervise(&supervisor, projection.clone());
+    assert_eq!(handles.len(), 1);
+
+    // the failing event is applied again once restarted
+    add(&repo, 2).await;
+    projection.wait_for(3).await;
+
+    let status = supervisor.status("total-added", ADDED).unwrap();
+    assert_eq!(status.state(), ProcessorState::Running);
+    assert_eq!(status.restarts(), 1);
+    assert_eq!(status.handled(), 2);
+    assert_eq!(status.checkpoint(), Some(1));
+    assert_eq!(
+        status.last_error(),
+        Some("projection : read model unavailable")
+    );
+}
+
+#[tokio::test]
+async fn sql_checkpoint_case() {
+    // an in memory sqlite database only lives in its connection
+    let pool = AnyPoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+
+    let storage = SqlStorage::new(pool);
+    storage.migrate().await.unwrap();
+
+    let checkpoints = SqlCheckpointStore::new(storage);
+
+    assert_eq!(checkpoints.load("consumer", ADDED).await.unwrap(), None);
+
+    checkpoints.save("consumer", ADDED, 3).await.unwrap();
+    checkpoints.save("consumer", ADDED, 5).await.unwrap();
+    checkpoints.save("other", ADDED, 1).await.unwrap();
+
+    assert_eq!(checkpoints.load("consumer", ADDED).await.unwrap(), Some(5));
+
+    checkpoints.clear("consumer").await.unwrap();
+
+    assert_eq!(checkpoints.load("consumer", ADDED).await.unwrap(), None);
+    assert_eq!(checkpoints.load("other", ADDED).await.unwrap(), Some(1));
+}
@@ -0,0 +1,110 @@
+use crate::common::FaultyStorage;
+use crate::simple::{SimpleCommand, SimpleState};
+use state_repository::error::{CommandError, RepositoryError};
+use state_repository::model_key::ModelKey;
+use state_repository::options::CommandOptions;
+use state_repository::retry::RetryPolicy;
+use state_repository::snapshot::InMemorySnapshotStore;
+use state_repository::StateRepository;
+use tokio::time::Duration;
+use uuid::Uuid;
+
+mod common;
+mod simple;
+
+fn get_repository() -> StateRepository {
+    let storage = FaultyStorage::default();
+    storage.conflict_appends();
+
+    StateRepository::new(storage, InMemorySnapshotStore::default())
+}
+
+fn get_key() -> ModelKey {
+    ModelKey::new("retry_test".to_string(), Uuid::new_v4().to_string())
+}
+
+#[tokio::test]
+async fn exhausted_case() {
+    let repo = get_repository().with_retry_policy(
+        RetryPolicy::default()
+            .max_attempts(3)
+            .initial_backoff(Duration::from_millis(1)),
+    );
+
+    let added = repo
+        .add_command::<SimpleState>(&get_key(), SimpleCommand::Add(1), None)
+        .await;
+
+    assert!(matches!(
+        added,
+        Err(CommandError::Repository(RepositoryError::RetryExhausted {
+            attempts: 3,
+            ..
+        }))
+    ));
+    assert_eq!(repo.conflict_count(), 3);
+}
+
+#[tokio::test]
+async fn per_call_case() {
+    let repo = get_repository();
+
+    let options = CommandOptions::default().retry_policy(RetryPolicy::no_retry());
+
+    let added = repo
+        .add_command_with_options::<SimpleState>(&get_key(), SimpleCommand::Add(1), None, &options)
+        .await;
+
+    assert!(matches!(
+        added,
+        Err(CommandError::Repository(RepositoryError::RetryExhausted {
+            attempts: 1,
+            ..
+        }))
+    ));
+    assert_eq!(repo.conflict_count(), 1);
+}
+
+#[tokio::test]
+async fn deadline_case() {
+    let repo = get_repository().with_retry_policy(
+        RetryPolicy::default()
+            .max_attempts(100)
+            .initial_backoff(Duration::from_millis(20))
+            .jitter(false)
+            .deadline(Some(Duration::from_millis(50))),
+    );
+
+    let added = repo
+        .add_command::<SimpleState>(&get_key(), SimpleCommand::Add(1), None)
+        .await;
+
+    // 20ms then 40ms of backoff goes past the deadline
+    assert!(matches!(
+        added,
+        Err(CommandError::Repository(RepositoryError::RetryExhausted {
+            attempts: 2,
+            ..
+        }))
+    ));
+}
+
+#[test]
+fn backoff_case() {
+    let policy = RetryPolicy::default()
+        .initial_backoff(Duration::from_millis(10))
+        .max_backoff(Duration::from_millis(50))
+        .jitter(false);
+
+    assert_eq!(policy.backoff(1), Duration::from_millis(10));
+    assert_eq!(policy.backoff(2), Duration::from_millis(20));
+    assert_eq!(policy.backoff(3), Duration::from_millis(40));
+    assert_eq!(policy.backoff(4), Duration::from_millis(50));
+
+    let policy = policy.jitter(true);
+
+    for attempt in 1..10 {
+        let backoff = policy.backoff(attempt);
+        assert!(backoff >= Duration::from_millis(5) && backoff <= Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,345 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use state::{Command, CommandContext, CommandName, Event, EventName, State, StateName};
+use state_repository::error::RepositoryError;
+use state_repository::model_key::ModelKey;
+use state_repository::saga::{Dispatch, Saga, SagaAction};
+use state_repository::storage::StoredEvent;
+use state_repository::waiter::ActionId;
+use state_repository::StateRepository;
+use tokio::time::Duration;
+
+const STOCK_STATE_PREFIX: &str = "test-stock";
+const TOWER_SAGA_PREFIX: &str = "test-tower-saga";
+const RESERVED: &str = "reserved";
+
+pub const DEADLINE: Duration = Duration::from_secs(3600);
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum StockCommand {
+    Reserve(u32),
+    Refill(u32),
+}
+
+impl Command for StockCommand {
+    fn command_name(&self) -> CommandName {
+        use StockCommand::*;
+        match &self {
+            Reserve(_) => "Reserve",
+            Refill(_) => "Refill",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum StockEvent {
+    Reserved(u32),
+    Refilled(u32),
+}
+
+impl Event for StockEvent {
+    fn event_name(&self) -> EventName {
+        use StockEvent::*;
+        match &self {
+            Reserved(_) => RESERVED,
+            Refilled(_) => "refilled",
+        }
+    }
+}
+
+/// Stock of gold or wood.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct StockState {
+    pub nb: u32,
+}
+
+impl Default for StockState {
+    fn default() -> Self {
+        StockState { nb: 100 }
+    }
+}
+
+impl State for StockState {
+    type Event = StockEvent;
+    type Command = StockCommand;
+    type Error = anyhow::Error;
+
+    fn name_prefix() -> StateName {
+        STOCK_STATE_PREFIX
+    }
+
+    fn play_event(&mut self, event: &Self::Event) {
+        match event {
+            StockEvent::Reserved(n) => self.nb -= n,
+            StockEvent::Refilled(n) => self.nb += n,
+        }
+    }
+
+    fn try_command(
+        &self,
+        command: Self::Command,
+        _context: &CommandContext,
+    ) -> Result<Vec<Self::Event>> {
+        match command {
+            StockCommand::Reserve(n) if n > self.nb => Err(anyhow!("{} missing", n - self.nb)),
+            StockCommand::Reserve(n) => Ok(vec![StockEvent::Reserved(n)]),
+            StockCommand::Refill(n) => Ok(vec![StockEvent::Refilled(n)]),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TowerOrder {
+    pub gold_bank: ModelKey,
+    pub wood_bank: ModelKey,
+    pub gold: u32,
+    pub wood: u32,
+    pub duration: Duration,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Refund {
+    pub bank: ModelKey,
+    pub amount: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum TowerCommand {
+    Start(TowerOrder),
+    GoldReserved,
+    WoodReserved,
+    Construct,
+    Fail(String),
+}
+
+impl Command for TowerCommand {
+    fn command_name(&self) -> CommandName {
+        use TowerCommand::*;
+        match &self {
+            Start(_) => "Start",
+            GoldReserved => "GoldReserved",
+            WoodReserved => "WoodReserved",
+            Construct => "Construct",
+            Fail(_) => "Fail",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum TowerEvent {
+    Started(TowerOrder, ActionId),
+    GoldReserved(TowerOrder),
+    WoodReserved(ActionId, Duration),
+    Built(ActionId),
+    Failed {
+        reason: String,
+        deadline: ActionId,
+        gold: Option<Refund>,
+        wood: Option<Refund>,
+    },
+}
+
+impl Event for TowerEvent {
+    fn event_name(&self) -> EventName {
+        use TowerEvent::*;
+        match &self {
+            Started(_, _) => "started",
+            GoldReserved(_) => "gold_reserved",
+            WoodReserved(_, _) => "wood_reserved",
+            Built(_) => "built",
+            Failed { .. } => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub enum TowerStep {
+    #[default]
+    Idle,
+    ReservingGold,
+    ReservingWood,
+    Constructing,
+    Built,
+    Failed(String),
+}
+
+/// Build a tower: reserve gold, reserve wood, wait for the construction, refunding what
+/// was reserved on failure or once the deadline is over.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TowerSaga {
+    pub order: Option<TowerOrder>,
+    pub deadline: Option<ActionId>,
+    pub step: TowerStep,
+}
+
+impl State for TowerSaga {
+    type Event = TowerEvent;
+    type Command = TowerCommand;
+    type Error = anyhow::Error;
+
+    fn name_prefix() -> StateName {
+        TOWER_SAGA_PREFIX
+    }
+
+    fn play_event(&mut self, event: &Self::Event) {
+        use TowerEvent::*;
+        match event {
+            Started(order, deadline) => {
+                self.order = Some(order.clone());
+                self.deadline = Some(*deadline);
+                self.step = TowerStep::ReservingGold;
+            }
+            GoldReserved(_) => self.step = TowerStep::ReservingWood,
+            WoodReserved(_, _) => self.step = TowerStep::Constructing,
+            Built(_) => self.step = TowerStep::Built,
+            Failed { reason, .. } => self.step = TowerStep::Failed(reason.clone()),
+        }
+    }
+
+    fn try_command(
+        &self,
+        command: Self::Command,
+        context: &CommandContext,
+    ) -> Result<Vec<Self::Event>> {
+        use TowerCommand::*;
+
+        let (order, deadline) = match (&self.order, self.deadline) {
+            (Some(order), Some(deadline)) => (order.clone(), deadline),
+            _ => {
+                return match command {
+                    Start(order) => Ok(vec![TowerEvent::Started(order, context.new_id())]),
+                    command => Err(anyhow!("{command:?} refused, tower not started")),
+                }
+            }
+        };
+
+        let gold = Refund {
+            bank: order.gold_bank.clone(),
+            amount: order.gold,
+        };
+        let wood = Refund {
+            bank: order.wood_bank.clone(),
+            amount: order.wood,
+        };
+
+        let refunds = match &self.step {
+            TowerStep::ReservingGold => Some((None, None)),
+            TowerStep::ReservingWood => Some((Some(gold), None)),
+            TowerStep::Constructing => Some((Some(gold), Some(wood))),
+            _ => None,
+        };
+
+        match (command, &self.step, refunds) {
+            (GoldReserved, TowerStep::ReservingGold, _) => {
+                Ok(vec![TowerEvent::GoldReserved(order)])
+            }
+            (WoodReserved, TowerStep::ReservingWood, _) => Ok(vec![TowerEvent::WoodReserved(
+                context.new_id(),
+                order.duration,
+            )]),
+            (Construct, TowerStep::Constructing, _) => Ok(vec![TowerEvent::Built(deadline)]),
+            (Fail(reason), _, Some((gold, wood))) => Ok(vec![TowerEvent::Failed {
+                reason,
+                deadline,
+                gold,
+                wood,
+            }]),
+            (command, step, _) => Err(anyhow!("{command:?} refused while {step:?}")),
+        }
+    }
+}
+
+impl Saga for TowerSaga {
+    fn reacted_event_types() -> Vec<String> {
+        vec![format!("evt.{STOCK_STATE_PREFIX}.{RESERVED}")]
+    }
+
+    fn action_events() -> Vec<EventName> {
+        vec![
+            "started",
+            "gold_reserved",
+            "wood_reserved",
+            "built",
+            "failed",
+        ]
+    }
+
+    fn react(
+        &self,
+        repo: &StateRepository,
+        event: &StoredEvent,
+    ) -> Result<Option<Self::Command>, RepositoryError> {
+        let reserved = match repo.decode_event::<StockEvent>(event)? {
+            StockEvent::Reserved(_) => true,
+            StockEvent::Refilled(_) => false,
+        };
+
+        match (&self.step, reserved) {
+            (TowerStep::ReservingGold, true) => Ok(Some(TowerCommand::GoldReserved)),
+            (TowerStep::ReservingWood, true) => Ok(Some(TowerCommand::WoodReserved)),
+            _ => Ok(None),
+        }
+    }
+
+    fn actions(event: Self::Event, _saga_key: &ModelKey) -> Vec<SagaAction<Self::Command>> {
+        use TowerEvent::*;
+        match event {
+            Started(order, deadline) => vec![
+                SagaAction::Dispatch {
+                    dispatch: Dispatch::new::<StockState>(
+                        order.gold_bank,
+                        StockCommand::Reserve(order.gold),
+                    ),
+                    on_rejected: Some(TowerCommand::Fail("gold missing".to_string())),
+                },
+                SagaAction::Timeout {
+                    id: deadline,
+                    command: TowerCommand::Fail("deadline".to_string()),
+                    delay: DEADLINE,
+                },
+            ],
+            GoldReserved(order) => vec![SagaAction::Dispatch {
+                dispatch: Dispatch::new::<StockState>(
+                    order.wood_bank,
+                    StockCommand::Reserve(order.wood),
+                ),
+                on_rejected: Some(TowerCommand::Fail("wood missing".to_string())),
+            }],
+            WoodReserved(id, duration) => vec![SagaAction::Timeout {
+                id,
+                command: TowerCommand::Construct,
+                delay: duration,
+            }],
+            Built(deadline) => vec![SagaAction::CancelTimeout { id: deadline }],
+            Failed {
+                deadline,
+                gold,
+                wood,
+                ..
+            } => {
+                let mut actions = vec![SagaAction::CancelTimeout { id: deadline }];
+
+                if let Some(gold) = gold {
+                    actions.push(SagaAction::Dispatch {
+                        dispatch: Dispatch::new::<StockState>(
+                            gold.bank,
+                            StockCommand::Refill(gold.amount),
+                        ),
+                        on_rejected: None,
+                    });
+                }
+                if let Some(wood) = wood {
+                    actions.push(SagaAction::Dispatch {
+                        dispatch: Dispatch::new::<StockState>(
+                            wood.bank,
+                            StockCommand::Refill(wood.amount),
+                        ),
+                        on_rejected: None,
+                    });
+                }
+
+                actions
+            }
+        }
+    }
+}
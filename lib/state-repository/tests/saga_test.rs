@@ -0,0 +1,209 @@
+use crate::common::{eventually, FaultyScheduleStore};
+use crate::saga::{StockState, TowerCommand, TowerOrder, TowerSaga, TowerStep, DEADLINE};
+use state::State;
+use state_repository::checkpoint::InMemoryCheckpointStore;
+use state_repository::clock::{Clock, ManualClock};
+use state_repository::consumer::Supervisor;
+use state_repository::model_key::ModelKey;
+use state_repository::saga::Saga;
+use state_repository::schedule::{InMemoryScheduleStore, ScheduleStore, Scheduler};
+use state_repository::snapshot::InMemorySnapshotStore;
+use state_repository::storage::InMemoryStorage;
+use state_repository::StateRepository;
+use std::time::SystemTime;
+use tokio::time::Duration;
+use uuid::Uuid;
+
+mod common;
+mod saga;
+
+const SAGA_CONSUMER: &str = "test-tower-saga.saga";
+
+fn get_repository() -> StateRepository {
+    StateRepository::new(InMemoryStorage::default(), InMemorySnapshotStore::default())
+}
+
+/// Start a tower saga, returning its key with the keys of the gold and wood banks.
+async fn start_tower(
+    repo: &StateRepository,
+    supervisor: &Supervisor,
+    scheduler: &Scheduler,
+    gold: u32,
+    wood: u32,
+    duration: Duration,
+) -> (ModelKey, ModelKey, ModelKey) {
+    TowerSaga::process_saga(supervisor, scheduler.clone())
+        .await
+        .unwrap();
+
+    let gold_bank = ModelKey::new("gold_test".to_string(), Uuid::new_v4().to_string());
+    let wood_bank = ModelKey::new("wood_test".to_string(), Uuid::new_v4().to_string());
+
+    let order = TowerOrder {
+        gold_bank: gold_bank.clone(),
+        wood_bank: wood_bank.clone(),
+        gold,
+        wood,
+        duration,
+    };
+
+    let key = TowerSaga::start(repo, TowerCommand::Start(order))
+        .await
+        .unwrap();
+
+    (key, gold_bank, wood_bank)
+}
+
+async fn step(repo: &StateRepository, key: &ModelKey) -> TowerStep {
+    repo.get_model::<TowerSaga>(key)
+        .await
+        .unwrap()
+        .state()
+        .step
+        .clone()
+}
+
+async fn stock(repo: &StateRepository, bank: &ModelKey) -> u32 {
+    repo.get_model::<StockState>(bank).await.unwrap().state().nb
+}
+
+/// Wait until the saga reaches `expected`.
+async fn reached(repo: &StateRepository, key: &ModelKey, expected: TowerStep) {
+    eventually(&format!("the saga {expected:?}"), || async {
+        step(repo, key).await == expected
+    })
+    .await;
+}
+
+/// Wait until the banks hold `gold` and `wood`, as once the saga dispatched its commands.
+async fn banks(
+    repo: &StateRepository,
+    gold_bank: &ModelKey,
+    wood_bank: &ModelKey,
+    gold: u32,
+    wood: u32,
+) {
+    eventually("the banks", || async {
+        stock(repo, gold_bank).await == gold && stock(repo, wood_bank).await == wood
+    })
+    .await;
+}
+
+/// Wait until no timeout is scheduled before `due`.
+async fn no_timeout(store: &impl ScheduleStore, due: SystemTime) {
+    eventually("the cancelled timeouts", || async {
+        store.due(due).await.unwrap().is_empty()
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn built_case() {
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let store = InMemoryScheduleStore::default();
+    let scheduler = Scheduler::new(repo.clone(), store.clone()).with_clock(clock.clone());
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default());
+
+    let (key, gold_bank, wood_bank) = start_tower(
+        &repo,
+        &supervisor,
+        &scheduler,
+        60,
+        50,
+        Duration::from_secs(10),
+    )
+    .await;
+
+    reached(&repo, &key, TowerStep::Constructing).await;
+    banks(&repo, &gold_bank, &wood_bank, 40, 50).await;
+
+    clock.advance(Duration::from_secs(10));
+
+    assert_eq!(scheduler.tick().await.unwrap(), 1);
+    assert_eq!(step(&repo, &key).await, TowerStep::Built);
+
+    // the deadline is cancelled once built
+    no_timeout(&store, clock.now() + DEADLINE).await;
+}
+
+#[tokio::test]
+async fn compensation_case() {
+    let repo = get_repository();
+    let scheduler = Scheduler::new(repo.clone(), InMemoryScheduleStore::default());
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default());
+
+    let (key, gold_bank, wood_bank) = start_tower(
+        &repo,
+        &supervisor,
+        &scheduler,
+        60,
+        150,
+        Duration::from_secs(10),
+    )
+    .await;
+
+    reached(&repo, &key, TowerStep::Failed("wood missing".to_string())).await;
+
+    // the gold paid before the wood was refused is refunded
+    banks(&repo, &gold_bank, &wood_bank, 100, 100).await;
+}
+
+#[tokio::test]
+async fn deadline_case() {
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let scheduler =
+        Scheduler::new(repo.clone(), InMemoryScheduleStore::default()).with_clock(clock.clone());
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default());
+
+    let (key, gold_bank, wood_bank) =
+        start_tower(&repo, &supervisor, &scheduler, 60, 50, DEADLINE * 2).await;
+
+    reached(&repo, &key, TowerStep::Constructing).await;
+
+    clock.advance(DEADLINE);
+
+    assert_eq!(scheduler.tick().await.unwrap(), 1);
+    assert_eq!(
+        step(&repo, &key).await,
+        TowerStep::Failed("deadline".to_string())
+    );
+
+    banks(&repo, &gold_bank, &wood_bank, 100, 100).await;
+
+    // the construction ending after the deadline is refused
+    clock.advance(DEADLINE);
+
+    assert_eq!(scheduler.tick().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn cancelled_before_scheduled_case() {
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let store = FaultyScheduleStore::default();
+    let scheduler = Scheduler::new(repo.clone(), store.clone()).with_clock(clock.clone());
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default());
+
+    // the failure cancels the deadline while the start is still scheduling it
+    store.slow_adds(Duration::from_millis(200));
+
+    let (key, _, _) = start_tower(&repo, &supervisor, &scheduler, 150, 50, DEADLINE).await;
+
+    reached(&repo, &key, TowerStep::Failed("gold missing".to_string())).await;
+
+    for event_name in ["started", "failed"] {
+        let event_type = format!("evt.{}.{event_name}", TowerSaga::name_prefix());
+
+        eventually(&format!("the {event_name} actions"), || async {
+            supervisor
+                .status(SAGA_CONSUMER, &event_type)
+                .map(|status| status.handled())
+                == Some(1)
+        })
+        .await;
+    }
+
+    assert!(store.due(clock.now() + DEADLINE).await.unwrap().is_empty());
+}
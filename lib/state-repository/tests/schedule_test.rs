@@ -0,0 +1,335 @@
+use crate::common::FaultyScheduleStore;
+use crate::simple::{SimpleCommand, SimpleState};
+use serde_json::json;
+use sqlx::any::AnyPoolOptions;
+use state::State;
+use state_repository::checkpoint::InMemoryCheckpointStore;
+use state_repository::clock::{Clock, ManualClock};
+use state_repository::consumer::Supervisor;
+use state_repository::model_key::ModelKey;
+use state_repository::retry::RetryPolicy;
+use state_repository::schedule::{
+    InMemoryScheduleStore, ScheduleStore, ScheduledCommand, Scheduler, SqlScheduleStore,
+    SCHEDULER_CONSUMER,
+};
+use state_repository::snapshot::InMemorySnapshotStore;
+use state_repository::storage::{InMemoryStorage, SqlStorage};
+use state_repository::StateRepository;
+use tokio::time::{sleep, timeout, Duration};
+use uuid::Uuid;
+
+mod common;
+mod simple;
+
+fn get_repository() -> StateRepository {
+    StateRepository::new(InMemoryStorage::default(), InMemorySnapshotStore::default())
+}
+
+fn get_key() -> ModelKey {
+    ModelKey::new("schedule_test".to_string(), Uuid::new_v4().to_string())
+}
+
+async fn sqlite_storage() -> SqlStorage {
+    // an in memory sqlite database only lives in its connection
+    let pool = AnyPoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+
+    let storage = SqlStorage::new(pool);
+    storage.migrate().await.unwrap();
+    storage
+}
+
+async fn nb(repo: &StateRepository, key: &ModelKey) -> u32 {
+    repo.get_model::<SimpleState>(key).await.unwrap().state().nb
+}
+
+#[tokio::test]
+async fn due_case() {
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let scheduler =
+        Scheduler::new(repo.clone(), InMemoryScheduleStore::default()).with_clock(clock.clone());
+
+    let key = get_key();
+
+    scheduler
+        .schedule::<SimpleState>(
+            Uuid::new_v4(),
+            &key,
+            SimpleCommand::Add(3),
+            Duration::from_secs(10),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(scheduler.tick().await.unwrap(), 0);
+
+    clock.advance(Duration::from_secs(10));
+
+    assert_eq!(scheduler.tick().await.unwrap(), 1);
+    assert_eq!(nb(&repo, &key).await, 3);
+
+    assert_eq!(scheduler.tick().await.unwrap(), 0, "applied only once");
+    assert_eq!(nb(&repo, &key).await, 3);
+}
+
+#[tokio::test]
+async fn cancel_case() {
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let scheduler =
+        Scheduler::new(repo.clone(), InMemoryScheduleStore::default()).with_clock(clock.clone());
+
+    let key = get_key();
+    let id = Uuid::new_v4();
+
+    scheduler
+        .schedule::<SimpleState>(
+            id,
+            &key,
+            SimpleCommand::Add(3),
+            Duration::from_secs(10),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert!(scheduler.cancel(id).await.unwrap());
+    assert!(!scheduler.cancel(id).await.unwrap());
+
+    clock.advance(Duration::from_secs(10));
+
+    assert_eq!(scheduler.tick().await.unwrap(), 0);
+    assert_eq!(nb(&repo, &key).await, 0);
+}
+
+/// Reschedule then cancel commands before they are scheduled, as when the events of
+/// their actions are received out of order.
+async fn out_of_order<S>(store: S)
+where
+    S: ScheduleStore + 'static,
+{
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let scheduler = Scheduler::new(repo.clone(), store).with_clock(clock.clone());
+
+    let key = get_key();
+    let (sped_up, cancelled) = (Uuid::new_v4(), Uuid::new_v4());
+
+    assert!(!scheduler
+        .reschedule(sped_up, Duration::from_secs(1))
+        .await
+        .unwrap());
+    assert!(!scheduler.cancel(cancelled).await.unwrap());
+
+    for (id, command) in [
+        (sped_up, SimpleCommand::Add(3)),
+        (cancelled, SimpleCommand::Add(5)),
+    ] {
+        scheduler
+            .schedule::<SimpleState>(id, &key, command, Duration::from_secs(10), None)
+            .await
+            .unwrap();
+    }
+
+    clock.advance(Duration::from_secs(1));
+
+    assert_eq!(scheduler.tick().await.unwrap(), 1, "rescheduled before");
+    assert_eq!(nb(&repo, &key).await, 3);
+
+    clock.advance(Duration::from_secs(10));
+
+    assert_eq!(scheduler.tick().await.unwrap(), 0, "cancelled before");
+    assert_eq!(nb(&repo, &key).await, 3);
+}
+
+#[tokio::test]
+async fn out_of_order_case() {
+    out_of_order(InMemoryScheduleStore::default()).await;
+}
+
+#[tokio::test]
+async fn sql_out_of_order_case() {
+    out_of_order(SqlScheduleStore::new(sqlite_storage().await)).await;
+}
+
+#[tokio::test]
+async fn rejected_case() {
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let scheduler =
+        Scheduler::new(repo.clone(), InMemoryScheduleStore::default()).with_clock(clock.clone());
+
+    let key = get_key();
+
+    scheduler
+        .schedule::<SimpleState>(
+            Uuid::new_v4(),
+            &key,
+            SimpleCommand::Remove(3),
+            Duration::ZERO,
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(scheduler.tick().await.unwrap(), 0);
+
+    repo.add_command::<SimpleState>(&key, SimpleCommand::Add(5), None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        scheduler.tick().await.unwrap(),
+        0,
+        "a refused command is dropped"
+    );
+    assert_eq!(nb(&repo, &key).await, 5);
+}
+
+#[tokio::test]
+async fn sql_restart_case() {
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let store = SqlScheduleStore::new(sqlite_storage().await);
+
+    let key = get_key();
+    let id = Uuid::new_v4();
+
+    let scheduler = Scheduler::new(repo.clone(), store.clone()).with_clock(clock.clone());
+
+    for _ in 0..2 {
+        scheduler
+            .schedule::<SimpleState>(
+                id,
+                &key,
+                SimpleCommand::Add(3),
+                Duration::from_secs(10),
+                None,
+            )
+            .await
+            .unwrap();
+    }
+
+    drop(scheduler);
+
+    clock.advance(Duration::from_secs(10));
+
+    let due = store.due(clock.now()).await.unwrap();
+    assert_eq!(due.len(), 1, "scheduled once");
+    assert_eq!(due[0].key(), &key);
+
+    // a new scheduler only applies the commands of the states it knows
+    let scheduler = Scheduler::new(repo.clone(), store.clone()).with_clock(clock.clone());
+
+    assert_eq!(scheduler.tick().await.unwrap(), 0);
+
+    scheduler.register::<SimpleState>();
+
+    assert_eq!(scheduler.tick().await.unwrap(), 1);
+    assert_eq!(nb(&repo, &key).await, 3);
+    assert!(store.due(clock.now()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn undecodable_case() {
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let store = InMemoryScheduleStore::default();
+    let scheduler = Scheduler::new(repo.clone(), store.clone()).with_clock(clock.clone());
+
+    let key = get_key();
+    let broken = Uuid::new_v4();
+
+    // a command of an older version of the state, due before a valid one
+    store
+        .add(ScheduledCommand::new(
+            broken,
+            clock.now(),
+            key.clone(),
+            SimpleState::name_prefix(),
+            json!({ "Multiply": 2 }),
+            None,
+        ))
+        .await
+        .unwrap();
+
+    scheduler
+        .schedule::<SimpleState>(
+            Uuid::new_v4(),
+            &key,
+            SimpleCommand::Add(3),
+            Duration::from_secs(1),
+            None,
+        )
+        .await
+        .unwrap();
+
+    clock.advance(Duration::from_secs(1));
+
+    assert_eq!(scheduler.tick().await.unwrap(), 1);
+    assert_eq!(nb(&repo, &key).await, 3);
+    assert!(store.due(clock.now()).await.unwrap().is_empty());
+
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default());
+    let dead_letters = supervisor.dead_letters(SCHEDULER_CONSUMER).await.unwrap();
+
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].event_id(), broken);
+    assert_eq!(dead_letters[0].data(), &json!({ "Multiply": 2 }));
+}
+
+#[tokio::test]
+async fn supervised_case() {
+    let repo = get_repository();
+    let clock = ManualClock::default();
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default())
+        .with_restart_policy(
+            RetryPolicy::default()
+                .initial_backoff(Duration::from_millis(10))
+                .jitter(false),
+        );
+    let store = FaultyScheduleStore::default();
+    store.fail_dues(1);
+    let scheduler = Scheduler::new(repo.clone(), store)
+        .with_clock(clock.clone())
+        .with_poll_interval(Duration::from_millis(10));
+
+    let key = get_key();
+
+    scheduler
+        .schedule::<SimpleState>(
+            Uuid::new_v4(),
+            &key,
+            SimpleCommand::Add(3),
+            Duration::ZERO,
+            None,
+        )
+        .await
+        .unwrap();
+
+    let handle = scheduler.start(&supervisor);
+
+    // the failing store only delays the command
+    timeout(Duration::from_secs(5), async {
+        while nb(&repo, &key).await != 3 {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("command applied after the store came back");
+
+    let status = supervisor.status(SCHEDULER_CONSUMER, "scheduled").unwrap();
+    assert_eq!(status.restarts(), 1);
+    assert_eq!(status.handled(), 1);
+    assert_eq!(
+        status.last_error(),
+        Some("schedule : schedule store unavailable")
+    );
+
+    handle.abort();
+}
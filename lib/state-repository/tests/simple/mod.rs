@@ -1,7 +1,6 @@
-use anyhow::anyhow;
-use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use state::{Command, Event, State};
+use state::{Command, CommandContext, Event, State};
+use std::fmt;
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub enum SimpleCommand {
@@ -35,6 +34,23 @@ impl Event for SimpleEvent {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum SimpleError {
+    Overflow { nb: u32, added: u32 },
+    Underflow { nb: u32, removed: u32 },
+}
+
+impl fmt::Display for SimpleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimpleError::Overflow { nb, added } => write!(f, "{added} cannot be added to {nb}"),
+            SimpleError::Underflow { nb, removed } => {
+                write!(f, "{removed} cannot be removed to {nb}")
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
 pub struct SimpleState {
     pub nb: u32,
@@ -43,6 +59,7 @@ pub struct SimpleState {
 impl State for SimpleState {
     type Event = SimpleEvent;
     type Command = SimpleCommand;
+    type Error = SimpleError;
 
     fn name_prefix() -> &'static str {
         "test-simple"
@@ -54,18 +71,28 @@ impl State for SimpleState {
         }
     }
 
-    fn try_command(&self, command: Self::Command) -> Result<Vec<Self::Event>> {
+    fn try_command(
+        &self,
+        command: Self::Command,
+        _context: &CommandContext,
+    ) -> Result<Vec<Self::Event>, Self::Error> {
         match command {
             SimpleCommand::Add(n) => {
                 if self.nb.checked_add(n).is_none() {
-                    Err(anyhow!("{} cannot be added to {}", n, self.nb))
+                    Err(SimpleError::Overflow {
+                        nb: self.nb,
+                        added: n,
+                    })
                 } else {
                     Ok(vec![SimpleEvent::Added(n)])
                 }
             }
             SimpleCommand::Remove(n) => {
                 if n > self.nb {
-                    Err(anyhow!("{} cannot be removed to {}", n, self.nb))
+                    Err(SimpleError::Underflow {
+                        nb: self.nb,
+                        removed: n,
+                    })
                 } else {
                     Ok(vec![SimpleEvent::Removed(n)])
                 }
@@ -73,7 +100,7 @@ impl State for SimpleState {
             SimpleCommand::Set(n) => Ok(vec![SimpleEvent::Removed(self.nb), SimpleEvent::Added(n)]),
         }
     }
-    fn state_cache_interval() -> Option<u64> {
+    fn snapshot_interval() -> Option<u64> {
         None
     }
 }
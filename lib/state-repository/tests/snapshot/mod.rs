@@ -0,0 +1,70 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use state::{Command, CommandContext, Event, State};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub enum CounterCommand {
+    Increment,
+}
+
+impl Command for CounterCommand {
+    fn command_name(&self) -> &'static str {
+        match &self {
+            CounterCommand::Increment => "Increment",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum CounterEvent {
+    Incremented,
+}
+
+impl Event for CounterEvent {
+    fn event_name(&self) -> &'static str {
+        match &self {
+            CounterEvent::Incremented => "incremented",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CounterState {
+    pub nb: u32,
+}
+
+pub const COUNTER_VERSION: u32 = 2;
+
+impl State for CounterState {
+    type Event = CounterEvent;
+    type Command = CounterCommand;
+    type Error = anyhow::Error;
+
+    fn name_prefix() -> &'static str {
+        "test-counter"
+    }
+
+    fn play_event(&mut self, event: &Self::Event) {
+        match event {
+            CounterEvent::Incremented => self.nb += 1,
+        }
+    }
+
+    fn try_command(
+        &self,
+        command: Self::Command,
+        _context: &CommandContext,
+    ) -> Result<Vec<Self::Event>> {
+        match command {
+            CounterCommand::Increment => Ok(vec![CounterEvent::Incremented]),
+        }
+    }
+
+    fn snapshot_interval() -> Option<u64> {
+        Some(4)
+    }
+
+    fn snapshot_version() -> u32 {
+        COUNTER_VERSION
+    }
+}
@@ -0,0 +1,142 @@
+use crate::snapshot::{CounterCommand, CounterState, COUNTER_VERSION};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use state_repository::model_key::ModelKey;
+use state_repository::snapshot::{
+    EventSnapshotStore, InMemorySnapshotStore, Snapshot, SnapshotStore,
+};
+use state_repository::storage::InMemoryStorage;
+use state_repository::StateRepository;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+mod snapshot;
+
+/// Snapshot store whose snapshots can neither be loaded nor saved.
+struct BrokenSnapshotStore;
+
+#[async_trait]
+impl SnapshotStore for BrokenSnapshotStore {
+    async fn load(&self, _key: &ModelKey) -> Result<Option<Snapshot>> {
+        Err(anyhow!("snapshot store unavailable"))
+    }
+
+    async fn save(&self, _key: &ModelKey, _snapshot: Snapshot) -> Result<()> {
+        Err(anyhow!("snapshot store unavailable"))
+    }
+}
+
+fn get_key() -> ModelKey {
+    ModelKey::new("snapshot_test".to_string(), Uuid::new_v4().to_string())
+}
+
+async fn increment(repo: &StateRepository, key: &ModelKey, times: u32) {
+    for _ in 0..times {
+        repo.add_command::<CounterState>(key, CounterCommand::Increment, None)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn interval_case() {
+    let snapshots = InMemorySnapshotStore::default();
+    let repo = StateRepository::new(InMemoryStorage::default(), snapshots.clone());
+    let key = get_key();
+
+    // a command and its event are two revisions
+    increment(&repo, &key, 1).await;
+    repo.get_model::<CounterState>(&key).await.unwrap();
+    assert_eq!(snapshots.load(&key).await.unwrap(), None);
+
+    increment(&repo, &key, 1).await;
+    repo.get_model::<CounterState>(&key).await.unwrap();
+    let snapshot = snapshots.load(&key).await.unwrap().unwrap();
+    assert_eq!(snapshot.revision(), 3);
+    assert_eq!(snapshot.version(), COUNTER_VERSION);
+    assert_eq!(
+        snapshot.state::<CounterState>().unwrap(),
+        CounterState { nb: 2 }
+    );
+
+    increment(&repo, &key, 1).await;
+    repo.get_model::<CounterState>(&key).await.unwrap();
+    assert_eq!(snapshots.load(&key).await.unwrap().unwrap().revision(), 3);
+
+    increment(&repo, &key, 1).await;
+    let model = repo.get_model::<CounterState>(&key).await.unwrap();
+    assert_eq!(model.state(), &CounterState { nb: 4 });
+    assert_eq!(snapshots.load(&key).await.unwrap().unwrap().revision(), 7);
+}
+
+#[tokio::test]
+async fn version_case() {
+    let snapshots = InMemorySnapshotStore::default();
+    let repo = StateRepository::new(InMemoryStorage::default(), snapshots.clone());
+    let key = get_key();
+
+    increment(&repo, &key, 1).await;
+
+    // the snapshot is used instead of the events before its revision
+    let snapshot = Snapshot::new(COUNTER_VERSION, 1, &CounterState { nb: 10 }).unwrap();
+    snapshots.save(&key, snapshot).await.unwrap();
+
+    let model = repo.get_model::<CounterState>(&key).await.unwrap();
+    assert_eq!(model.state(), &CounterState { nb: 10 });
+
+    // a snapshot of an other version is ignored
+    let snapshot = Snapshot::new(COUNTER_VERSION - 1, 1, &CounterState { nb: 10 }).unwrap();
+    snapshots.save(&key, snapshot).await.unwrap();
+
+    let model = repo.get_model::<CounterState>(&key).await.unwrap();
+    assert_eq!(model.state(), &CounterState { nb: 1 });
+}
+
+#[tokio::test]
+async fn failure_case() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let hooked = errors.clone();
+    let repo = StateRepository::new(InMemoryStorage::default(), BrokenSnapshotStore)
+        .with_snapshot_failure_hook(move |error| hooked.lock().unwrap().push(error.to_string()));
+    let key = get_key();
+
+    // the snapshots are a cache, the stream is replayed without them
+    increment(&repo, &key, 2).await;
+    let model = repo.get_model::<CounterState>(&key).await.unwrap();
+    assert_eq!(model.state(), &CounterState { nb: 2 });
+    assert!(repo.snapshot_failure_count() > 0);
+    let errors = errors.lock().unwrap().clone();
+    assert_eq!(errors.len() as u64, repo.snapshot_failure_count());
+    assert!(errors
+        .iter()
+        .all(|error| error.contains("snapshot store unavailable")));
+
+    // a snapshot which no longer decodes is replayed without it too
+    let snapshots = InMemorySnapshotStore::default();
+    let repo = StateRepository::new(InMemoryStorage::default(), snapshots.clone());
+    let key = get_key();
+
+    increment(&repo, &key, 1).await;
+    let snapshot = Snapshot::new(COUNTER_VERSION, 1, &"not a counter").unwrap();
+    snapshots.save(&key, snapshot).await.unwrap();
+
+    let model = repo.get_model::<CounterState>(&key).await.unwrap();
+    assert_eq!(model.state(), &CounterState { nb: 1 });
+    assert_eq!(repo.snapshot_failure_count(), 1);
+}
+
+#[tokio::test]
+async fn event_db_case() {
+    let snapshots = EventSnapshotStore::new(InMemoryStorage::default());
+    let key = get_key();
+
+    assert_eq!(snapshots.load(&key).await.unwrap(), None);
+
+    let first = Snapshot::new(COUNTER_VERSION, 3, &CounterState { nb: 2 }).unwrap();
+    snapshots.save(&key, first).await.unwrap();
+
+    let second = Snapshot::new(COUNTER_VERSION, 7, &CounterState { nb: 4 }).unwrap();
+    snapshots.save(&key, second.clone()).await.unwrap();
+
+    assert_eq!(snapshots.load(&key).await.unwrap(), Some(second));
+}
@@ -0,0 +1,340 @@
+use crate::simple::{SimpleCommand, SimpleEvent, SimpleState};
+use futures::StreamExt;
+use state::State;
+use state_repository::error::RepositoryError;
+use state_repository::metadata::EventWithMetadata;
+use state_repository::model_key::ModelKey;
+use state_repository::storage::{EventStorage, SubscriptionStart};
+use std::collections::HashSet;
+use tokio::time::{timeout, Duration};
+use uuid::Uuid;
+
+fn command_with_event(
+    command: SimpleCommand,
+    event: SimpleEvent,
+) -> (EventWithMetadata, EventWithMetadata) {
+    let command = EventWithMetadata::from_command(command, None, SimpleState::name_prefix());
+    let event =
+        EventWithMetadata::from_event(event, command.metadata(), SimpleState::name_prefix());
+    (command, event)
+}
+
+pub async fn expected_revision_case(storage: &impl EventStorage) {
+    let key = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+
+    let (command, event) = command_with_event(SimpleCommand::Add(3), SimpleEvent::Added(3));
+
+    assert!(storage.read_last_event(&key).await.unwrap().is_none());
+
+    let appended = storage
+        .append_events(&key, Some(0), vec![command.clone(), event.clone()])
+        .await;
+    assert!(
+        matches!(appended, Err(RepositoryError::Conflict(_))),
+        "stream does not exist yet"
+    );
+
+    storage
+        .append_events(&key, None, vec![command.clone(), event.clone()])
+        .await
+        .unwrap();
+
+    let appended = storage
+        .append_events(&key, None, vec![command.clone(), event.clone()])
+        .await;
+    assert!(
+        matches!(appended, Err(RepositoryError::Conflict(_))),
+        "stream already exists"
+    );
+
+    let appended = storage
+        .append_events(&key, Some(0), vec![command.clone(), event.clone()])
+        .await;
+    assert!(
+        matches!(appended, Err(RepositoryError::Conflict(_))),
+        "stream is at revision 1"
+    );
+
+    storage
+        .append_events(&key, Some(1), vec![command, event])
+        .await
+        .unwrap();
+
+    let events = storage.read_events(&key, None).await.unwrap();
+    let revisions: Vec<u64> = events.iter().map(|e| e.revision()).collect();
+    assert_eq!(revisions, vec![0, 1, 2, 3]);
+
+    let events = storage.read_events(&key, Some(3)).await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].revision(), 3);
+
+    let last = storage.read_last_event(&key).await.unwrap().unwrap();
+    assert_eq!(last.revision(), 3);
+}
+
+pub async fn idempotency_key_case(storage: &impl EventStorage) {
+    let key = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+
+    let (command, event) = command_with_event(SimpleCommand::Add(3), SimpleEvent::Added(3));
+    storage
+        .append_events(&key, None, vec![command, event])
+        .await
+        .unwrap();
+
+    let (command, event) = command_with_event(SimpleCommand::Add(5), SimpleEvent::Added(5));
+    let command = command.with_idempotency_key(Some("add-5".to_string()));
+    storage
+        .append_events(&key, Some(1), vec![command, event])
+        .await
+        .unwrap();
+
+    let found = storage
+        .read_idempotency_key(&key, "add-5")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.revision(), 2);
+
+    assert!(storage
+        .read_idempotency_key(&key, "add-3")
+        .await
+        .unwrap()
+        .is_none());
+
+    let other_key = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+    assert!(storage
+        .read_idempotency_key(&other_key, "add-5")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+pub async fn metadata_case(storage: &impl EventStorage) {
+    let key = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+
+    let (command, event) = command_with_event(SimpleCommand::Add(3), SimpleEvent::Added(3));
+
+    storage
+        .append_events(&key, None, vec![command.clone(), event.clone()])
+        .await
+        .unwrap();
+
+    let events = storage.read_events(&key, None).await.unwrap();
+
+    let command_metadata = events[0].metadata().unwrap();
+    assert_eq!(&command_metadata, command.metadata());
+    assert!(!command_metadata.is_event());
+
+    let event_metadata = events[1].metadata().unwrap();
+    assert_eq!(&event_metadata, event.metadata());
+    assert!(event_metadata.is_event());
+    assert_eq!(event_metadata.correlation_id(), command.id());
+    assert_eq!(event_metadata.causation_id(), command.id());
+
+    assert_eq!(events[1].key(), key);
+    assert_eq!(events[1].event_type(), "evt.test-simple.added");
+    assert!(matches!(
+        events[1].as_json::<SimpleEvent>().unwrap(),
+        SimpleEvent::Added(3)
+    ));
+}
+
+pub async fn event_type_case(storage: &impl EventStorage) {
+    let key_one = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+    let key_two = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+
+    let mut subscription = storage
+        .subscribe_event_type("evt.test-simple.removed", SubscriptionStart::End)
+        .await
+        .unwrap();
+
+    let (command, added) = command_with_event(SimpleCommand::Add(3), SimpleEvent::Added(3));
+    storage
+        .append_events(&key_one, None, vec![command, added])
+        .await
+        .unwrap();
+
+    let (command, removed) = command_with_event(SimpleCommand::Remove(2), SimpleEvent::Removed(2));
+    storage
+        .append_events(&key_one, Some(1), vec![command, removed.clone()])
+        .await
+        .unwrap();
+
+    let (command, other_removed) =
+        command_with_event(SimpleCommand::Remove(1), SimpleEvent::Removed(1));
+    storage
+        .append_events(&key_two, None, vec![command, other_removed.clone()])
+        .await
+        .unwrap();
+
+    let received = subscription.next().await.unwrap().unwrap();
+    assert_eq!(received.id(), removed.id());
+    assert_eq!(received.key(), key_one);
+
+    let received = subscription.next().await.unwrap().unwrap();
+    assert_eq!(received.id(), other_removed.id());
+    assert_eq!(received.key(), key_two);
+
+    let removed_events = storage
+        .read_event_type("evt.test-simple.removed")
+        .await
+        .unwrap();
+    assert_eq!(removed_events.len(), 2);
+}
+
+pub async fn subscription_start_case(storage: &impl EventStorage) {
+    let event_type = "evt.test-simple.added";
+
+    let mut appended = Vec::new();
+    for _ in 0..2 {
+        let key = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+        let (command, added) = command_with_event(SimpleCommand::Add(1), SimpleEvent::Added(1));
+        storage
+            .append_events(&key, None, vec![command, added.clone()])
+            .await
+            .unwrap();
+        appended.push(added);
+    }
+
+    let stored = storage.read_event_type(event_type).await.unwrap();
+    let first_position = stored[0].position().unwrap();
+    assert!(stored[1].position().unwrap() > first_position);
+
+    let mut from_beginning = storage
+        .subscribe_event_type(event_type, SubscriptionStart::Beginning)
+        .await
+        .unwrap();
+    let mut after_first = storage
+        .subscribe_event_type(event_type, SubscriptionStart::After(first_position))
+        .await
+        .unwrap();
+    let mut from_end = storage
+        .subscribe_event_type(event_type, SubscriptionStart::End)
+        .await
+        .unwrap();
+
+    let key = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+    let (command, added) = command_with_event(SimpleCommand::Add(1), SimpleEvent::Added(1));
+    storage
+        .append_events(&key, None, vec![command, added.clone()])
+        .await
+        .unwrap();
+    appended.push(added);
+
+    for expected in &appended {
+        let received = from_beginning.next().await.unwrap().unwrap();
+        assert_eq!(received.id(), expected.id());
+    }
+
+    for expected in &appended[1..] {
+        let received = after_first.next().await.unwrap().unwrap();
+        assert_eq!(received.id(), expected.id());
+    }
+
+    let received = from_end.next().await.unwrap().unwrap();
+    assert_eq!(received.id(), appended[2].id());
+    assert!(received.position().unwrap() > stored[1].position().unwrap());
+}
+
+pub async fn correlation_case(storage: &impl EventStorage) {
+    let key_one = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+    let key_two = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+
+    let (command, added) = command_with_event(SimpleCommand::Add(3), SimpleEvent::Added(3));
+    let correlation_id = command.id();
+
+    let caused = EventWithMetadata::from_command(
+        SimpleCommand::Remove(2),
+        Some(added.metadata()),
+        SimpleState::name_prefix(),
+    );
+    let removed = EventWithMetadata::from_event(
+        SimpleEvent::Removed(2),
+        caused.metadata(),
+        SimpleState::name_prefix(),
+    );
+
+    storage
+        .append_events(&key_one, None, vec![command.clone(), added.clone()])
+        .await
+        .unwrap();
+
+    // an other correlation in the same stream
+    let (other_command, other_added) =
+        command_with_event(SimpleCommand::Add(1), SimpleEvent::Added(1));
+    storage
+        .append_events(&key_one, Some(1), vec![other_command, other_added])
+        .await
+        .unwrap();
+
+    storage
+        .append_events(&key_two, None, vec![caused.clone(), removed.clone()])
+        .await
+        .unwrap();
+
+    let events = storage.read_correlation(correlation_id).await.unwrap();
+    let ids: Vec<Uuid> = events.iter().map(|e| e.id()).collect();
+    assert_eq!(
+        ids,
+        vec![command.id(), added.id(), caused.id(), removed.id()]
+    );
+    assert_eq!(events[2].key(), key_two);
+    assert!(events.iter().all(|e| e.created().is_some()));
+
+    let events = storage.read_correlation(Uuid::new_v4()).await.unwrap();
+    assert!(events.is_empty());
+}
+
+/// Appends racing each other while a subscription follows them: every event is
+/// delivered once, by increasing position.
+pub async fn concurrent_append_case<S>(storage: S)
+where
+    S: EventStorage + Clone + 'static,
+{
+    let event_type = "evt.test-simple.removed";
+
+    let mut subscription = storage
+        .subscribe_event_type(event_type, SubscriptionStart::End)
+        .await
+        .unwrap();
+
+    let appends: Vec<_> = (0..50)
+        .map(|_| {
+            let storage = storage.clone();
+            tokio::spawn(async move {
+                let key = ModelKey::new("storage_test".to_string(), Uuid::new_v4().to_string());
+                let (command, removed) =
+                    command_with_event(SimpleCommand::Remove(1), SimpleEvent::Removed(1));
+                storage
+                    .append_events(&key, None, vec![command, removed.clone()])
+                    .await
+                    .unwrap();
+                removed.id()
+            })
+        })
+        .collect();
+
+    let mut appended = HashSet::new();
+    for append in appends {
+        appended.insert(append.await.unwrap());
+    }
+
+    let mut received = HashSet::new();
+    let mut last_position = None;
+
+    while received.len() < appended.len() {
+        let event = timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .expect("every appended event is delivered")
+            .unwrap()
+            .unwrap();
+
+        let position = event.position();
+        assert!(position > last_position, "delivered by increasing position");
+        last_position = position;
+
+        assert!(received.insert(event.id()), "delivered once");
+    }
+
+    assert_eq!(received, appended);
+}
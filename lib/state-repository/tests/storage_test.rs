@@ -0,0 +1,183 @@
+use crate::simple::{SimpleCommand, SimpleState};
+use futures::StreamExt;
+use sqlx::any::AnyPoolOptions;
+use state_repository::model_key::ModelKey;
+use state_repository::snapshot::InMemorySnapshotStore;
+use state_repository::storage::{EventStorage, InMemoryStorage, SqlStorage, SubscriptionStart};
+use state_repository::StateRepository;
+use tokio::time::{timeout, Duration};
+use uuid::Uuid;
+
+mod simple;
+mod storage;
+
+async fn get_sql_storage() -> SqlStorage {
+    // an in memory sqlite database only lives in its connection
+    let pool = AnyPoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+
+    let storage = SqlStorage::new(pool).with_poll_interval(Duration::from_millis(10));
+    storage.migrate().await.unwrap();
+
+    storage
+}
+
+/// Database in a file, so that several connections append at once.
+async fn get_shared_sql_storage() -> SqlStorage {
+    let path = std::env::temp_dir().join(format!("storage_test_{}.db", Uuid::new_v4()));
+
+    let pool = AnyPoolOptions::new()
+        .max_connections(4)
+        .connect(&format!("sqlite://{}?mode=rwc", path.display()))
+        .await
+        .unwrap();
+
+    let storage = SqlStorage::new(pool).with_poll_interval(Duration::from_millis(10));
+    storage.migrate().await.unwrap();
+
+    storage
+}
+
+#[tokio::test]
+async fn memory_expected_revision_case() {
+    storage::expected_revision_case(&InMemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn memory_idempotency_key_case() {
+    storage::idempotency_key_case(&InMemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn memory_metadata_case() {
+    storage::metadata_case(&InMemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn memory_event_type_case() {
+    storage::event_type_case(&InMemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn memory_subscription_start_case() {
+    storage::subscription_start_case(&InMemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn memory_correlation_case() {
+    storage::correlation_case(&InMemoryStorage::default()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn memory_concurrent_append_case() {
+    storage::concurrent_append_case(InMemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn sql_expected_revision_case() {
+    storage::expected_revision_case(&get_sql_storage().await).await;
+}
+
+#[tokio::test]
+async fn sql_idempotency_key_case() {
+    storage::idempotency_key_case(&get_sql_storage().await).await;
+}
+
+#[tokio::test]
+async fn sql_metadata_case() {
+    storage::metadata_case(&get_sql_storage().await).await;
+}
+
+#[tokio::test]
+async fn sql_event_type_case() {
+    storage::event_type_case(&get_sql_storage().await).await;
+}
+
+#[tokio::test]
+async fn sql_subscription_start_case() {
+    storage::subscription_start_case(&get_sql_storage().await).await;
+}
+
+#[tokio::test]
+async fn sql_correlation_case() {
+    storage::correlation_case(&get_sql_storage().await).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sql_concurrent_append_case() {
+    storage::concurrent_append_case(get_shared_sql_storage().await).await;
+}
+
+/// Row of `event_type` at `position`, as committed by an append.
+async fn insert_at(storage: &SqlStorage, position: i64, event_type: &str) {
+    sqlx::query(
+        "INSERT INTO events (position, stream_id, revision, event_id, event_type, data, metadata) \
+         VALUES (?, ?, 0, ?, ?, '{}', '{}')",
+    )
+    .bind(position)
+    .bind(Uuid::new_v4().to_string())
+    .bind(Uuid::new_v4().to_string())
+    .bind(event_type)
+    .execute(storage.pool())
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn sql_gap_case() {
+    let storage = get_sql_storage()
+        .await
+        .with_gap_timeout(Duration::from_millis(300));
+    let event_type = "evt.test-simple.added";
+
+    let mut subscription = storage
+        .subscribe_event_type(event_type, SubscriptionStart::Beginning)
+        .await
+        .unwrap();
+
+    // an append commits its position before the one started first
+    insert_at(&storage, 2, event_type).await;
+    assert!(timeout(Duration::from_millis(100), subscription.next())
+        .await
+        .is_err());
+
+    insert_at(&storage, 1, event_type).await;
+
+    // an append rolled back leaves its position missing for good
+    insert_at(&storage, 4, event_type).await;
+
+    for position in [1, 2, 4] {
+        let event = timeout(Duration::from_secs(1), subscription.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(event.position(), Some(position));
+    }
+}
+
+#[tokio::test]
+async fn sql_repository_case() {
+    let repo = StateRepository::new(get_sql_storage().await, InMemorySnapshotStore::default());
+
+    let key = ModelKey::new("sql_test".to_string(), Uuid::new_v4().to_string());
+
+    let added = repo
+        .add_command::<SimpleState>(&key, SimpleCommand::Add(17), None)
+        .await
+        .unwrap();
+
+    assert_eq!(added, (SimpleState { nb: 17 }));
+
+    repo.add_command::<SimpleState>(&key, SimpleCommand::Set(50), None)
+        .await
+        .unwrap();
+
+    let model = repo.get_model::<SimpleState>(&key).await.unwrap();
+
+    assert_eq!(model.state(), &SimpleState { nb: 50 });
+}
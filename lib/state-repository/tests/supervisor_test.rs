@@ -0,0 +1,330 @@
+use crate::common::{eventually, FaultyStorage};
+use crate::cross_state::build::{BuildCommand, BuildState, BuildingCreate};
+use crate::cross_state::build_api::{PaymentQuestion, PAYMENT_ASKED};
+use crate::cross_state::gold::{GoldState, GOLD_STATE_NAME};
+use serde::{Deserialize, Serialize};
+use state::{Event, EventName};
+use state_repository::checkpoint::InMemoryCheckpointStore;
+use state_repository::consumer::{ProcessorState, Supervisor};
+use state_repository::cross_state::{CrossStateAnswer, CrossStateQuestion};
+use state_repository::dead_letter::dead_letter_event_type;
+use state_repository::metadata::{EventWithMetadata, Metadata};
+use state_repository::model_key::ModelKey;
+use state_repository::retry::RetryPolicy;
+use state_repository::snapshot::InMemorySnapshotStore;
+use state_repository::storage::InMemoryStorage;
+use state_repository::StateRepository;
+use tokio::time::Duration;
+use uuid::Uuid;
+
+mod common;
+mod cross_state;
+
+/// Payment question the bank cannot read.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct MalformedQuestion {
+    amount: String,
+}
+
+impl Event for MalformedQuestion {
+    fn event_name(&self) -> EventName {
+        PAYMENT_ASKED
+    }
+
+    fn is_state_specific(&self) -> bool {
+        false
+    }
+}
+
+fn get_repository() -> StateRepository {
+    StateRepository::new(InMemoryStorage::default(), InMemorySnapshotStore::default())
+}
+
+async fn built(repo: &StateRepository, key: &ModelKey) -> bool {
+    repo.get_model::<BuildState>(key)
+        .await
+        .unwrap()
+        .state()
+        .built
+}
+
+async fn create_build(repo: &StateRepository, cost: u32) -> (ModelKey, ModelKey) {
+    let key = ModelKey::new("tower_test".to_string(), Uuid::new_v4().to_string());
+    let key_bank = ModelKey::new("bank_test".to_string(), Uuid::new_v4().to_string());
+
+    let create = BuildingCreate {
+        cost,
+        bank: key_bank.clone(),
+    };
+
+    repo.add_command::<BuildState>(&key, BuildCommand::Create(create), None)
+        .await
+        .unwrap();
+
+    (key, key_bank)
+}
+
+/// Ask a payment the bank cannot read, returning the id of the question.
+async fn ask_malformed(repo: &StateRepository) -> Uuid {
+    let question = EventWithMetadata::from_event(
+        MalformedQuestion {
+            amount: "a lot".to_string(),
+        },
+        &Metadata::new(None, Uuid::new_v4(), Uuid::new_v4(), true),
+        "test-malformed",
+    );
+    let question_id = question.id();
+    let key = ModelKey::new("malformed_test".to_string(), Uuid::new_v4().to_string());
+
+    repo.event_db()
+        .append_events(&key, None, vec![question])
+        .await
+        .unwrap();
+
+    question_id
+}
+
+#[tokio::test]
+async fn dead_letter_case() {
+    let repo = get_repository();
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default());
+
+    BuildState::process_query(&supervisor).await.unwrap();
+    GoldState::process_question(&supervisor).await.unwrap();
+
+    let question_id = ask_malformed(&repo).await;
+
+    // the questions after the malformed one are still answered
+    let (key, _) = create_build(&repo, 322).await;
+
+    eventually("the build", || built(&repo, &key)).await;
+
+    let event_type = format!("evt.{PAYMENT_ASKED}");
+    let consumer = format!("{GOLD_STATE_NAME}.cross");
+
+    eventually("both questions", || async {
+        supervisor
+            .status(&consumer, &event_type)
+            .map(|status| status.handled())
+            == Some(2)
+    })
+    .await;
+
+    let status = supervisor.status(&consumer, &event_type).unwrap();
+    assert_eq!(status.state(), ProcessorState::Running);
+    assert_eq!(status.handled(), 2);
+    assert_eq!(status.dead_letters(), 1);
+    assert_eq!(status.checkpoint(), Some(1));
+
+    let dead_letters = supervisor.dead_letters(&consumer).await.unwrap();
+    assert_eq!(dead_letters.len(), 1);
+
+    let dead_letter = &dead_letters[0];
+    assert_eq!(dead_letter.consumer(), consumer);
+    assert_eq!(dead_letter.event_id(), question_id);
+    assert_eq!(dead_letter.data()["amount"], "a lot");
+    assert!(dead_letter.error().starts_with("deserialization"));
+
+    let entries = repo
+        .event_db()
+        .read_event_type(&dead_letter_event_type(&consumer))
+        .await
+        .unwrap();
+    assert_eq!(entries[0].metadata().unwrap().causation_id(), question_id);
+}
+
+#[tokio::test]
+async fn retry_case() {
+    let storage = FaultyStorage::default();
+    storage.lock("bank_test");
+    let repo = StateRepository::new(storage.clone(), InMemorySnapshotStore::default());
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default());
+
+    BuildState::process_query(&supervisor).await.unwrap();
+    GoldState::process_question(&supervisor).await.unwrap();
+
+    let consumer = format!("{GOLD_STATE_NAME}.cross");
+
+    let (key, key_bank) = create_build(&repo, 322).await;
+
+    eventually("the dead letter", || async {
+        !supervisor.dead_letters(&consumer).await.unwrap().is_empty()
+    })
+    .await;
+
+    let dead_letters = supervisor.dead_letters(&consumer).await.unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].error(), "processing : bank_test locked");
+
+    let question_id = dead_letters[0].event_id();
+    let inspected = supervisor
+        .dead_letter(&consumer, question_id)
+        .await
+        .unwrap();
+    assert_eq!(inspected.as_ref(), Some(&dead_letters[0]));
+
+    storage.unlock();
+
+    assert!(supervisor
+        .retry_dead_letter(&consumer, question_id)
+        .await
+        .unwrap());
+
+    eventually("the build", || built(&repo, &key)).await;
+
+    let gold_state = repo.get_model::<GoldState>(&key_bank).await.unwrap();
+    assert_eq!(gold_state.state(), &GoldState { nb: 678 });
+
+    assert!(supervisor.dead_letters(&consumer).await.unwrap().is_empty());
+    assert!(!supervisor
+        .retry_dead_letter(&consumer, question_id)
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn discard_case() {
+    let repo = get_repository();
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default());
+
+    GoldState::process_question(&supervisor).await.unwrap();
+
+    let consumer = format!("{GOLD_STATE_NAME}.cross");
+
+    let question_id = ask_malformed(&repo).await;
+
+    eventually("the dead letter", || async {
+        supervisor
+            .dead_letter(&consumer, question_id)
+            .await
+            .unwrap()
+            .is_some()
+    })
+    .await;
+
+    assert!(supervisor
+        .discard_dead_letter(&consumer, question_id)
+        .await
+        .unwrap());
+    assert!(!supervisor
+        .discard_dead_letter(&consumer, question_id)
+        .await
+        .unwrap());
+
+    assert!(supervisor
+        .dead_letter(&consumer, question_id)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(supervisor.dead_letters(&consumer).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn restart_case() {
+    let storage = FaultyStorage::default();
+    storage.fail_subscriptions(1);
+    let repo = StateRepository::new(storage, InMemorySnapshotStore::default());
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default())
+        .with_restart_policy(RetryPolicy::default().initial_backoff(Duration::from_millis(10)));
+
+    let event_type = format!("evt.{PAYMENT_ASKED}");
+    let consumer = format!("{GOLD_STATE_NAME}.cross");
+
+    GoldState::process_question(&supervisor).await.unwrap();
+
+    let (_, key_bank) = create_build(&repo, 322).await;
+
+    eventually("the payment", || async {
+        repo.get_model::<GoldState>(&key_bank)
+            .await
+            .unwrap()
+            .state()
+            == &(GoldState { nb: 678 })
+    })
+    .await;
+
+    let status = supervisor.status(&consumer, &event_type).unwrap();
+    assert_eq!(status.state(), ProcessorState::Running);
+    assert_eq!(status.restarts(), 1);
+    assert_eq!(status.last_error(), Some("store unavailable : down"));
+}
+
+#[tokio::test]
+async fn max_in_flight_case() {
+    let storage = FaultyStorage::default();
+    storage.slow_reads(Duration::from_millis(20));
+    let repo = StateRepository::new(storage.clone(), InMemorySnapshotStore::default());
+
+    for _ in 0..8 {
+        create_build(&repo, 322).await;
+    }
+    storage.reset_max_reading();
+
+    let supervisor =
+        Supervisor::new(repo.clone(), InMemoryCheckpointStore::default()).with_max_in_flight(2);
+
+    GoldState::process_question(&supervisor).await.unwrap();
+
+    let event_type = format!("evt.{PAYMENT_ASKED}");
+    let consumer = format!("{GOLD_STATE_NAME}.cross");
+
+    eventually("the questions", || async {
+        supervisor
+            .status(&consumer, &event_type)
+            .map(|status| status.handled())
+            == Some(8)
+    })
+    .await;
+
+    // each question reads the bank once at a time
+    assert_eq!(storage.max_reading(), 2);
+}
+
+#[tokio::test]
+async fn stream_order_case() {
+    let storage = FaultyStorage::default();
+    storage.slow_reads(Duration::from_millis(20));
+    let repo = StateRepository::new(storage.clone(), InMemorySnapshotStore::default());
+
+    let key = ModelKey::new("tower_test".to_string(), Uuid::new_v4().to_string());
+    let key_bank = ModelKey::new("bank_test".to_string(), Uuid::new_v4().to_string());
+
+    // the questions of a single stream
+    let questions = (0..4)
+        .map(|_| {
+            EventWithMetadata::from_event(
+                PaymentQuestion {
+                    amount: 100,
+                    bank: key_bank.clone(),
+                },
+                &Metadata::new(None, Uuid::new_v4(), Uuid::new_v4(), true),
+                "test-build",
+            )
+        })
+        .collect();
+    repo.event_db()
+        .append_events(&key, None, questions)
+        .await
+        .unwrap();
+
+    let supervisor = Supervisor::new(repo.clone(), InMemoryCheckpointStore::default());
+
+    GoldState::process_question(&supervisor).await.unwrap();
+
+    let event_type = format!("evt.{PAYMENT_ASKED}");
+    let consumer = format!("{GOLD_STATE_NAME}.cross");
+
+    eventually("the questions", || async {
+        supervisor
+            .status(&consumer, &event_type)
+            .map(|status| status.handled())
+            == Some(4)
+    })
+    .await;
+
+    // handled one after the other, though more could be in flight
+    assert_eq!(storage.max_reading(), 1);
+
+    let gold_state = repo.get_model::<GoldState>(&key_bank).await.unwrap();
+    assert_eq!(gold_state.state(), &GoldState { nb: 600 });
+}
@@ -0,0 +1,118 @@
+use crate::profile::{ProfileCommand, ProfileEventV1, ProfileState};
+use anyhow::anyhow;
+use serde_json::{json, Value};
+use state::State;
+use state_repository::error::RepositoryError;
+use state_repository::metadata::EventWithMetadata;
+use state_repository::model_key::ModelKey;
+use state_repository::snapshot::InMemorySnapshotStore;
+use state_repository::storage::InMemoryStorage;
+use state_repository::upcast::Upcasters;
+use state_repository::StateRepository;
+use uuid::Uuid;
+
+mod profile;
+
+const RENAMED: &str = "evt.test-profile.renamed";
+
+fn get_key() -> ModelKey {
+    ModelKey::new("upcast_test".to_string(), Uuid::new_v4().to_string())
+}
+
+/// `{"Renamed": "first last"}` to `{"Renamed": {"first_name": "first", "last_name": "last"}}`
+fn split_name(data: Value) -> anyhow::Result<Value> {
+    let name = data["Renamed"]
+        .as_str()
+        .ok_or_else(|| anyhow!("no name in {data}"))?;
+    let (first_name, last_name) = name.split_once(' ').unwrap_or((name, ""));
+
+    Ok(json!({
+        "Renamed": { "first_name": first_name, "last_name": last_name }
+    }))
+}
+
+async fn append_v1(repo: &StateRepository, key: &ModelKey, name: &str) {
+    let command = EventWithMetadata::from_command(
+        ProfileCommand::Rename(String::new(), String::new()),
+        None,
+        ProfileState::name_prefix(),
+    );
+    let event = EventWithMetadata::from_event(
+        ProfileEventV1::Renamed(name.to_string()),
+        command.metadata(),
+        ProfileState::name_prefix(),
+    );
+
+    let revision = repo
+        .event_db()
+        .read_events(key, None)
+        .await
+        .unwrap()
+        .last()
+        .map(|e| e.revision());
+
+    repo.event_db()
+        .append_events(key, revision, vec![command, event])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn upcast_case() {
+    let repo = StateRepository::new(InMemoryStorage::default(), InMemorySnapshotStore::default())
+        .with_upcasters(Upcasters::default().register(RENAMED, 1, split_name));
+
+    let key = get_key();
+
+    append_v1(&repo, &key, "Ada Lovelace").await;
+
+    let model = repo.get_model::<ProfileState>(&key).await.unwrap();
+    assert_eq!(
+        model.state(),
+        &ProfileState {
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+        }
+    );
+
+    // version 2 events are played as they are
+    repo.add_command::<ProfileState>(
+        &key,
+        ProfileCommand::Rename("Grace".to_string(), "Hopper".to_string()),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let events = repo.event_db().read_events(&key, None).await.unwrap();
+    assert_eq!(events[1].metadata().unwrap().event_version(), 1);
+    assert_eq!(events[3].metadata().unwrap().event_version(), 2);
+
+    let model = repo.get_model::<ProfileState>(&key).await.unwrap();
+    assert_eq!(model.state().first_name, "Grace");
+}
+
+#[tokio::test]
+async fn missing_upcaster_case() {
+    let repo = StateRepository::new(InMemoryStorage::default(), InMemorySnapshotStore::default());
+
+    let key = get_key();
+
+    append_v1(&repo, &key, "Ada Lovelace").await;
+
+    let model = repo.get_model::<ProfileState>(&key).await;
+    assert!(matches!(model, Err(RepositoryError::Deserialization(_))));
+}
+
+#[test]
+fn chain_case() {
+    let upcasters = Upcasters::default()
+        .register(RENAMED, 1, |data| Ok(json!({ "v2": data })))
+        .register(RENAMED, 2, |data| Ok(json!({ "v3": data })));
+
+    let data = upcasters.upcast(RENAMED, 1, json!(1)).unwrap();
+    assert_eq!(data, json!({ "v3": { "v2": 1 } }));
+
+    let data = upcasters.upcast(RENAMED, 3, json!(1)).unwrap();
+    assert_eq!(data, json!(1));
+}
@@ -1,8 +1,8 @@
 use anyhow::anyhow;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use state::{Command, CommandName, Event, EventName, State, StateName};
-use state_repository::waiter::DelayedState;
+use state::{Command, CommandContext, CommandName, Event, EventName, State, StateName};
+use state_repository::waiter::{ActionId, DelayedAction, DelayedState};
 use tokio::time::Duration;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -13,7 +13,7 @@ pub enum WaitCommand {
 }
 
 pub const GROWTH_STARTED: &str = "growth_started";
-const SINLGE_STATE_PREFIX: &'static str = "test-wait";
+const SINLGE_STATE_PREFIX: &str = "test-wait";
 
 impl Command for WaitCommand {
     fn command_name(&self) -> CommandName {
@@ -30,7 +30,7 @@ impl Command for WaitCommand {
 pub enum WaitEvent {
     Added(u32),
     Removed(u32),
-    GrowthStarted(u32, Duration),
+    GrowthStarted(u32, Duration, ActionId),
 }
 
 impl Event for WaitEvent {
@@ -40,7 +40,7 @@ impl Event for WaitEvent {
         match &self {
             Added(_) => "added",
             Removed(_) => "removed",
-            GrowthStarted(_, _) => GROWTH_STARTED,
+            GrowthStarted(_, _, _) => GROWTH_STARTED,
         }
     }
 }
@@ -53,6 +53,7 @@ pub struct WaitState {
 impl State for WaitState {
     type Event = WaitEvent;
     type Command = WaitCommand;
+    type Error = anyhow::Error;
 
     fn name_prefix() -> StateName {
         SINLGE_STATE_PREFIX
@@ -62,11 +63,15 @@ impl State for WaitState {
         match event {
             Added(n) => self.nb += n,
             Removed(n) => self.nb -= n,
-            GrowthStarted(_, _) => {}
+            GrowthStarted(_, _, _) => {}
         }
     }
 
-    fn try_command(&self, command: Self::Command) -> Result<Vec<Self::Event>> {
+    fn try_command(
+        &self,
+        command: Self::Command,
+        context: &CommandContext,
+    ) -> Result<Vec<Self::Event>> {
         use WaitCommand::*;
         use WaitEvent::*;
         match command {
@@ -84,11 +89,20 @@ impl State for WaitState {
                     Ok(vec![Added(n)])
                 }
             }
-            Growth(n) => Ok(vec![Removed(n), GrowthStarted(n, Duration::from_secs(2))]),
+            Growth(n) => {
+                if n > self.nb {
+                    Err(anyhow!("{} cannot grow from {}", n, self.nb))
+                } else {
+                    Ok(vec![
+                        Removed(n),
+                        GrowthStarted(n, Duration::from_secs(2), context.new_id()),
+                    ])
+                }
+            }
         }
     }
 
-    fn state_cache_interval() -> Option<u64> {
+    fn snapshot_interval() -> Option<u64> {
         None
     }
 }
@@ -98,11 +112,15 @@ impl DelayedState for WaitState {
         vec![GROWTH_STARTED]
     }
 
-    fn resolve_command(event: Self::Event) -> (Self::Command, Duration) {
+    fn resolve_action(event: Self::Event) -> DelayedAction<Self::Command> {
         use WaitCommand::*;
         use WaitEvent::*;
         match event {
-            GrowthStarted(n, d) => (GrowEnd(n * 2), d),
+            GrowthStarted(n, delay, id) => DelayedAction::Schedule {
+                id,
+                command: GrowEnd(n * 2),
+                delay,
+            },
             _ => {
                 unimplemented!()
             }
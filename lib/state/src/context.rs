@@ -0,0 +1,98 @@
+use crate::clock::{Clock, ManualClock, SystemClock};
+use std::fmt::{self, Debug};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Source of the ids created by commands.
+pub trait IdGenerator: Send + Sync {
+    fn next_id(&self) -> Uuid;
+}
+
+/// Random ids.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// Ids counting from 1, shared between its clones.
+#[derive(Clone, Debug, Default)]
+pub struct SequentialIds {
+    last: Arc<AtomicU64>,
+}
+
+impl IdGenerator for SequentialIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::from_u128(self.last.fetch_add(1, Ordering::Relaxed) as u128 + 1)
+    }
+}
+
+/// What a command may depend on besides the state, given to `try_command` so that it
+/// stays deterministic.
+#[derive(Clone)]
+pub struct CommandContext {
+    clock: Arc<dyn Clock>,
+    ids: Arc<dyn IdGenerator>,
+    actor: Option<String>,
+}
+
+impl Default for CommandContext {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(RandomIds),
+            actor: None,
+        }
+    }
+}
+
+impl Debug for CommandContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandContext")
+            .field("now", &self.now())
+            .field("actor", &self.actor)
+            .finish()
+    }
+}
+
+impl CommandContext {
+    /// Context of tests, its time staying at `now` and its ids counting from 1.
+    pub fn fixed(now: SystemTime) -> Self {
+        Self::default()
+            .with_clock(ManualClock::new(now))
+            .with_ids(SequentialIds::default())
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn with_ids(mut self, ids: impl IdGenerator + 'static) -> Self {
+        self.ids = Arc::new(ids);
+        self
+    }
+
+    /// Who sends the command, if known.
+    pub fn with_actor(mut self, actor: Option<String>) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    pub fn new_id(&self) -> Uuid {
+        self.ids.next_id()
+    }
+
+    pub fn actor(&self) -> Option<&str> {
+        self.actor.as_deref()
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{CommandContext, State};
+use proptest::collection::vec;
+use proptest::strategy::Strategy;
+use proptest::test_runner::{Config, TestCaseError, TestError, TestRunner};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::UNIX_EPOCH;
+
+type Invariant<S> = Box<dyn Fn(&S) -> bool>;
+
+type Transition<S> = Box<dyn Fn(&S, &<S as State>::Command, &S) -> bool>;
+
+/// Random sequences of commands tried from the default state, the events of the
+/// accepted ones being played.
+///
+/// A sequence fails when `try_command` or `play_event` panics, or when an invariant
+/// or a transition does not hold after a command. The failing sequence is shrunk to a shortest one,
+/// given in the panic message of `check`. Each sequence is tried with a fixed context,
+/// so that it gives the same events when replayed.
+pub struct InvariantCheck<S: State> {
+    invariants: Vec<(&'static str, Invariant<S>)>,
+    transitions: Vec<(&'static str, Transition<S>)>,
+    max_commands: usize,
+    cases: u32,
+}
+
+impl<S: State> Default for InvariantCheck<S> {
+    fn default() -> Self {
+        Self {
+            invariants: Vec::new(),
+            transitions: Vec::new(),
+            max_commands: 20,
+            cases: 256,
+        }
+    }
+}
+
+impl<S: State> InvariantCheck<S> {
+    /// Property which must hold for the state after every command, named in failures.
+    pub fn invariant(
+        mut self,
+        name: &'static str,
+        invariant: impl Fn(&S) -> bool + 'static,
+    ) -> Self {
+        self.invariants.push((name, Box::new(invariant)));
+        self
+    }
+
+    /// Property which must hold between the states before and after every accepted
+    /// command, named in failures.
+    pub fn transition(
+        mut self,
+        name: &'static str,
+        transition: impl Fn(&S, &S::Command, &S) -> bool + 'static,
+    ) -> Self {
+        self.transitions.push((name, Box::new(transition)));
+        self
+    }
+
+    /// Longest sequence of commands tried.
+    pub fn max_commands(mut self, max_commands: usize) -> Self {
+        self.max_commands = max_commands;
+        self
+    }
+
+    /// Number of sequences tried.
+    pub fn cases(mut self, cases: u32) -> Self {
+        self.cases = cases;
+        self
+    }
+
+    /// Try sequences of the commands given by `commands`, panicking with a shortest
+    /// failing sequence if any.
+    pub fn check(&self, commands: impl Strategy<Value = S::Command>) {
+        let mut runner = TestRunner::new(Config {
+            cases: self.cases,
+            ..Config::default()
+        });
+
+        let sequences = vec(commands, 0..=self.max_commands);
+
+        match runner.run(&sequences, |commands| self.replay(commands)) {
+            Ok(()) => {}
+            Err(TestError::Fail(reason, commands)) => {
+                panic!("{reason}, with the commands {commands:?}")
+            }
+            Err(TestError::Abort(reason)) => panic!("check aborted : {reason}"),
+        }
+    }
+
+    fn replay(&self, commands: Vec<S::Command>) -> Result<(), TestCaseError> {
+        let mut state = S::default();
+        let context = CommandContext::fixed(UNIX_EPOCH);
+
+        self.check_invariants(&state, "the default state")?;
+
+        for (index, command) in commands.into_iter().enumerate() {
+            let tried = format!("command {index} {command:?}");
+            let before = state.clone();
+
+            let events = catch_unwind(AssertUnwindSafe(|| {
+                state.try_command(command.clone(), &context)
+            }))
+            .map_err(|_| TestCaseError::fail(format!("try_command panicked on {tried}")))?;
+
+            // a rejected command leaves the state as it was
+            let events = match events {
+                Ok(events) => events,
+                Err(_) => continue,
+            };
+
+            for event in events {
+                catch_unwind(AssertUnwindSafe(|| state.play_event(&event))).map_err(|_| {
+                    TestCaseError::fail(format!("play_event panicked on {event:?} of {tried}"))
+                })?;
+            }
+
+            self.check_invariants(&state, &tried)?;
+            self.check_transitions(&before, &command, &state, &tried)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_transitions(
+        &self,
+        before: &S,
+        command: &S::Command,
+        after: &S,
+        tried: &str,
+    ) -> Result<(), TestCaseError> {
+        for (name, transition) in &self.transitions {
+            if !transition(before, command, after) {
+                return Err(TestCaseError::fail(format!(
+                    "transition {name} broken by {tried}, from {before:?} to {after:?}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_invariants(&self, state: &S, after: &str) -> Result<(), TestCaseError> {
+        for (name, invariant) in &self.invariants {
+            if !invariant(state) {
+                return Err(TestCaseError::fail(format!(
+                    "invariant {name} broken by {after}, state {state:?}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,8 +1,26 @@
-use anyhow::Result;
-
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
+
+#[cfg(feature = "derive")]
+pub use state_derive::{Command, Event};
+
+/// Time of the system, or a manual one for tests.
+pub mod clock;
+
+/// Clock, ids and origin given to `try_command`.
+pub mod context;
+
+pub use context::CommandContext;
+
+/// Invariants of a `State` checked on random sequences of commands.
+#[cfg(feature = "proptest")]
+pub mod invariant;
+
+/// Given/when/then checks of a `State` without any storage, for unit tests and for the
+/// cucumber steps of `state_world!`.
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub type CommandName = &'static str;
 pub type EventName = &'static str;
@@ -18,19 +36,80 @@ pub trait Event: Serialize + DeserializeOwned + Debug + Send + Clone {
     fn is_state_specific(&self) -> bool {
         true
     }
+
+    /// Version of the serialized event, to increase when its fields change. Older
+    /// versions are upcasted to the current one when they are read.
+    fn event_version(&self) -> u32 {
+        1
+    }
 }
 
 pub trait State: Default + Serialize + DeserializeOwned + Debug + Send + Clone {
     type Event: Event;
     type Command: Command + Sync + Send;
+    /// Domain error of a refused command, given back by `StateRepository::add_command`.
+    type Error: Debug + Display + Send + Sync + 'static;
 
     fn name_prefix() -> StateName;
 
     fn play_event(&mut self, event: &Self::Event);
 
-    fn try_command(&self, command: Self::Command) -> Result<Vec<Self::Event>>;
+    /// Events of an accepted command. The time and the new ids come from `context`, so
+    /// that the same command on the same state gives the same events in tests.
+    fn try_command(
+        &self,
+        command: Self::Command,
+        context: &CommandContext,
+    ) -> Result<Vec<Self::Event>, Self::Error>;
 
-    fn state_cache_interval() -> Option<u64> {
+    /// Revisions of the stream after the last snapshot before a new one is taken,
+    /// `None` to never snapshot the state.
+    fn snapshot_interval() -> Option<u64> {
         None
     }
+
+    #[deprecated(note = "renamed `snapshot_interval`")]
+    fn state_cache_interval() -> Option<u64> {
+        Self::snapshot_interval()
+    }
+
+    /// Version of the serialized state, to increase when its fields change so that
+    /// older snapshots are not used anymore.
+    fn snapshot_version() -> u32 {
+        0
+    }
+}
+
+/// Used by the code of `#[derive(Command)]` and `#[derive(Event)]`.
+#[doc(hidden)]
+pub mod __private {
+    /// Whether a name is given twice, evaluated at compile time.
+    pub const fn has_duplicates(names: &[&str]) -> bool {
+        let mut i = 0;
+        while i < names.len() {
+            let mut j = i + 1;
+            while j < names.len() {
+                if equals(names[i].as_bytes(), names[j].as_bytes()) {
+                    return true;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    const fn equals(a: &[u8], b: &[u8]) -> bool {
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut i = 0;
+        while i < a.len() {
+            if a[i] != b[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
 }
@@ -0,0 +1,210 @@
+use crate::{CommandContext, State};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::Debug;
+use std::time::UNIX_EPOCH;
+
+/// State built from its past events, on which a command is tried.
+///
+/// Commands are given a fixed context, at the unix epoch with ids counting from 1,
+/// unless an other one is given.
+#[derive(Debug)]
+pub struct StateHarness<S: State> {
+    state: S,
+    context: CommandContext,
+    outcome: Option<Result<Vec<S::Event>, S::Error>>,
+}
+
+impl<S: State> Default for StateHarness<S> {
+    fn default() -> Self {
+        Self {
+            state: S::default(),
+            context: CommandContext::fixed(UNIX_EPOCH),
+            outcome: None,
+        }
+    }
+}
+
+impl<S: State> StateHarness<S> {
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn given_state(&mut self, state: S) {
+        self.state = state;
+    }
+
+    pub fn given_event(&mut self, event: S::Event) {
+        self.state.play_event(&event);
+    }
+
+    /// Context given to the next commands.
+    pub fn given_context(&mut self, context: CommandContext) {
+        self.context = context;
+    }
+
+    /// Try a command, playing its events when it is accepted.
+    pub fn when(&mut self, command: S::Command) {
+        let outcome = self.state.try_command(command, &self.context);
+
+        if let Ok(events) = &outcome {
+            for event in events {
+                self.state.play_event(event);
+            }
+        }
+
+        self.outcome = Some(outcome);
+    }
+
+    /// Check the command gave these events, compared as json.
+    pub fn then_events(&self, expected: &[S::Event]) {
+        match &self.outcome {
+            Some(Ok(events)) => assert_eq!(
+                to_json(events),
+                to_json(expected),
+                "expected events {expected:?}, got {events:?}"
+            ),
+            Some(Err(error)) => panic!("expected events {expected:?}, got error {error}"),
+            None => panic!("no command tried"),
+        }
+    }
+
+    /// Check the command was rejected with an error containing `expected`, if any.
+    pub fn then_error(&self, expected: Option<&str>) {
+        match &self.outcome {
+            Some(Err(error)) => {
+                if let Some(expected) = expected {
+                    let message = error.to_string();
+                    assert!(
+                        message.contains(expected),
+                        "expected error containing {expected:?}, got {message:?}"
+                    );
+                }
+            }
+            Some(Ok(events)) => panic!("expected an error, got events {events:?}"),
+            None => panic!("no command tried"),
+        }
+    }
+
+    /// Check the state, compared as json.
+    pub fn then_state(&self, expected: &S) {
+        assert_eq!(
+            to_json(&self.state),
+            to_json(expected),
+            "expected state {expected:?}, got {:?}",
+            self.state
+        );
+    }
+}
+
+/// Start a check from the past events of a state:
+/// `given::<S>(events).when(command).then_events([...])`.
+pub fn given<S: State>(events: impl IntoIterator<Item = S::Event>) -> Given<S> {
+    let mut harness = StateHarness::default();
+
+    for event in events {
+        harness.given_event(event);
+    }
+
+    Given(harness)
+}
+
+pub struct Given<S: State>(StateHarness<S>);
+
+impl<S: State> Given<S> {
+    pub fn with_context(mut self, context: CommandContext) -> Self {
+        self.0.given_context(context);
+        self
+    }
+
+    pub fn when(mut self, command: S::Command) -> Then<S> {
+        self.0.when(command);
+        Then(self.0)
+    }
+}
+
+pub struct Then<S: State>(StateHarness<S>);
+
+impl<S: State> Then<S> {
+    /// Check the command gave these events, returning the state after them.
+    pub fn then_events(self, expected: impl IntoIterator<Item = S::Event>) -> S {
+        let expected: Vec<S::Event> = expected.into_iter().collect();
+        self.0.then_events(&expected);
+        self.0.state
+    }
+
+    /// Check the command was rejected with an error containing `expected`, returning the
+    /// unchanged state.
+    pub fn then_error(self, expected: &str) -> S {
+        self.0.then_error(Some(expected));
+        self.0.state
+    }
+}
+
+/// Read a value of a step, panicking when it is not the json of a `T`.
+pub fn from_json<T: DeserializeOwned>(json: &str) -> T {
+    serde_json::from_str(json).unwrap_or_else(|e| panic!("cannot read {json} : {e}"))
+}
+
+fn to_json<T: Serialize + Debug + ?Sized>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or_else(|e| panic!("cannot write {value:?} : {e}"))
+}
+
+/// Cucumber world of a state with the steps shared by every state, the values being
+/// written in json:
+///
+/// ```gherkin
+/// Given the event {"ReputationAdded": 20}
+/// When the command {"RemoveReputation": 22}
+/// Then the command is rejected with "cannot remove"
+/// ```
+///
+/// The crate calling it needs `cucumber` in its dev-dependencies, and can add its own
+/// steps working on `world.harness`.
+#[macro_export]
+macro_rules! state_world {
+    ($world:ident, $state:ty) => {
+        #[derive(Debug, Default, ::cucumber::World)]
+        pub struct $world {
+            pub harness: $crate::testing::StateHarness<$state>,
+        }
+
+        #[::cucumber::given(regex = r"^the state (.+)$")]
+        fn given_state(world: &mut $world, json: String) {
+            world.harness.given_state($crate::testing::from_json(&json));
+        }
+
+        #[::cucumber::given(regex = r"^the event (.+)$")]
+        fn given_event(world: &mut $world, json: String) {
+            world.harness.given_event($crate::testing::from_json(&json));
+        }
+
+        #[::cucumber::when(regex = r"^the command (.+)$")]
+        fn when_command(world: &mut $world, json: String) {
+            world.harness.when($crate::testing::from_json(&json));
+        }
+
+        #[::cucumber::then(regex = r"^the events are (.+)$")]
+        fn then_events(world: &mut $world, json: String) {
+            let expected: Vec<<$state as $crate::State>::Event> = $crate::testing::from_json(&json);
+            world.harness.then_events(&expected);
+        }
+
+        #[::cucumber::then(regex = r"^the command is rejected$")]
+        fn then_rejected(world: &mut $world) {
+            world.harness.then_error(None);
+        }
+
+        #[::cucumber::then(regex = r#"^the command is rejected with "(.+)"$"#)]
+        fn then_error(world: &mut $world, expected: String) {
+            world.harness.then_error(Some(expected.as_str()));
+        }
+
+        #[::cucumber::then(regex = r"^the state is (.+)$")]
+        fn then_state(world: &mut $world, json: String) {
+            let expected: $state = $crate::testing::from_json(&json);
+            world.harness.then_state(&expected);
+        }
+    };
+}
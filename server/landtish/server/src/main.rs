@@ -9,6 +9,7 @@ use rocket::fs::{relative, FileServer};
 use rocket::http::Method;
 use rocket::response::content;
 use rocket_cors::{AllowedHeaders, AllowedOrigins};
+use state_repository::snapshot::RedisSnapshotStore;
 use state_repository::StateRepository;
 
 pub struct AccountIssuer {}
@@ -36,9 +37,9 @@ fn rocket() -> _ {
     let settings = config.event_store().parse().unwrap();
     let event_db = Client::new(settings).unwrap();
 
-    let cache_db = redis::Client::open(config.redis()).unwrap();
+    let snapshot_db = RedisSnapshotStore::new(redis::Client::open(config.redis()).unwrap());
 
-    let state_repository = StateRepository::new(event_db, cache_db);
+    let state_repository = StateRepository::new(event_db, snapshot_db);
 
     let allowed_origins = AllowedOrigins::some_exact(&config.get_hosts());
 
@@ -1,23 +1,15 @@
 use serde::{Deserialize, Serialize};
-use state::{Command, CommandName};
+use state::Command;
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Info {
     pub pseudo: String,
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, Command)]
 pub enum LandtishCommand {
+    #[command(name = "join")]
     Join(Info),
+    #[command(name = "leave")]
     Leave(Info),
 }
-
-impl Command for LandtishCommand {
-
-    fn command_name(&self) -> CommandName {
-        match self {
-            LandtishCommand::Join(_) => "join",
-            LandtishCommand::Leave(_) => "leave",
-        }
-    }
-}
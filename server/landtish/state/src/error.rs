@@ -5,12 +5,14 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Responder, Clone, Debug, Deserialize, Eq, PartialEq, Serialize, Display)]
 pub enum LandtishError {
+    #[response(status = 422)]
+    NoPlayer(String),
     #[response(status = 500)]
     Other(String),
 }
 
 impl From<anyhow::Error> for LandtishError {
-    fn from(_: Error) -> Self {
-        Self::Other("Oupsi".to_string())
+    fn from(error: Error) -> Self {
+        Self::Other(error.to_string())
     }
 }
@@ -1,18 +1,10 @@
 use serde::{Deserialize, Serialize};
-use state::{Event, EventName};
+use state::Event;
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, Event)]
 pub enum LandtishEvent {
+    #[event(name = "joined")]
     Joined,
+    #[event(name = "leaved")]
     Leaved,
 }
-
-impl Event for LandtishEvent {
-
-    fn event_name(&self) -> EventName {
-        match self {
-            LandtishEvent::Joined => "joined",
-            LandtishEvent::Leaved => "leaved",
-        }
-    }
-}
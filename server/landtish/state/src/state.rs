@@ -1,8 +1,8 @@
-use anyhow::Result;
 use landtish_shared::LandtishCommand;
 use rocket::serde::{Deserialize, Serialize};
-use state::{State, StateName};
+use state::{CommandContext, State, StateName};
 
+use crate::error::LandtishError;
 use crate::LandtishEvent;
 
 #[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone)]
@@ -20,6 +20,7 @@ impl LandtishState {
 impl State for LandtishState {
     type Event = LandtishEvent;
     type Command = LandtishCommand;
+    type Error = LandtishError;
 
     fn name_prefix() -> StateName {
         "landtish"
@@ -27,18 +28,25 @@ impl State for LandtishState {
 
     fn play_event(&mut self, event: &Self::Event) {
         match event {
-            LandtishEvent::Joined => {}
-            LandtishEvent::Leaved => {}
+            LandtishEvent::Joined => self.nb_player += 1,
+            LandtishEvent::Leaved => self.nb_player -= 1,
         }
     }
 
-    fn try_command(&self, command: Self::Command) -> Result<Vec<Self::Event>> {
+    fn try_command(
+        &self,
+        command: Self::Command,
+        _context: &CommandContext,
+    ) -> Result<Vec<Self::Event>, Self::Error> {
         match command {
             LandtishCommand::Join(_) => Ok(vec![LandtishEvent::Joined]),
+            LandtishCommand::Leave(info) if self.nb_player == 0 => Err(LandtishError::NoPlayer(
+                format!("{} cannot leave, no player joined", info.pseudo),
+            )),
             LandtishCommand::Leave(_) => Ok(vec![LandtishEvent::Leaved]),
         }
     }
-    fn state_cache_interval() -> Option<u64> {
+    fn snapshot_interval() -> Option<u64> {
         Some(1)
     }
 }
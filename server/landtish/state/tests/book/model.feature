@@ -1 +1,17 @@
-Feature: Number feature
\ No newline at end of file
+Feature: Number feature
+
+    Scenario: a player can join
+        When the command {"Join": {"pseudo": "player"}}
+        Then the events are ["Joined"]
+        Then the state is {"nb_player": 1, "position": 0}
+
+    Scenario: a player can leave
+        Given the state {"nb_player": 1, "position": 0}
+        When the command {"Leave": {"pseudo": "player"}}
+        Then the events are ["Leaved"]
+        Then the state is {"nb_player": 0, "position": 0}
+
+    Scenario: a player cannot leave when nobody joined
+        When the command {"Leave": {"pseudo": "player"}}
+        Then the command is rejected with "player cannot leave, no player joined"
+        Then the state is {"nb_player": 0, "position": 0}
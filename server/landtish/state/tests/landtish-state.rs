@@ -1,19 +1,9 @@
-use landtish_state::event::LandtishEvent::Joined;
+use cucumber::World;
 use landtish_state::state::LandtishState;
 
-use landtish_shared::LandtishCommand::Join;
-use anyhow::Error;
-use cucumber::{given, then, when, World};
-use state::State;
-
-
-#[derive(cucumber::World, Debug, Default)]
-pub struct LandtishWorld {
-    model: LandtishState,
-    err: Option<Error>,
-}
+state::state_world!(LandtishWorld, LandtishState);
 
 #[tokio::main]
 async fn main() {
     LandtishWorld::run("tests/book").await;
-}
\ No newline at end of file
+}